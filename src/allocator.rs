@@ -0,0 +1,101 @@
+use std::alloc::Allocator;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] using a buffer from a custom
+/// allocator.
+///
+/// Created by the [`copy_with_allocator`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_allocator`]: fn.copy_with_allocator.html
+#[derive(Debug)]
+pub struct CopyWithAllocator<R, W, A: Allocator> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8], A>>,
+}
+
+/// Variant of [`copy_with_buffer`] which allocates a zeroed buffer of `size`
+/// bytes in `alloc`, e.g. a NUMA-local, huge-page or arena allocator. The
+/// buffer is returned on success as `Box<[u8], A>`.
+///
+/// Available with the `allocator_api` feature, which needs a nightly compiler.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_allocator<R, W, A>(reader: R, writer: W, size: usize, alloc: A)
+    -> CopyWithAllocator<R, W, A>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          A: Allocator,
+{
+    let mut buffer = Vec::with_capacity_in(size, alloc);
+    buffer.resize(size, 0);
+    CopyWithAllocator {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer.into_boxed_slice()),
+    }
+}
+
+impl<R, W, A> Future for CopyWithAllocator<R, W, A>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          A: Allocator,
+{
+    type Item = (u64, R, W, Box<[u8], A>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8], A>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
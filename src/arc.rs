@@ -0,0 +1,99 @@
+use std::io;
+use std::sync::Arc;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] using a reference-counted buffer.
+///
+/// Created by the [`copy_arc`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_arc`]: fn.copy_arc.html
+#[derive(Debug)]
+pub struct CopyArc<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Arc<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] taking an `Arc<[u8]>`, so the buffer
+/// returned on success can be cloned and handed to other consumers without
+/// copying.
+///
+/// # Panics
+///
+/// The future panics when polled if any other clone of `buffer` is alive,
+/// as it needs exclusive access to fill it.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_arc<R, W>(reader: R, writer: W, buffer: Arc<[u8]>) -> CopyArc<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyArc {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyArc<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Arc<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Arc<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = Arc::get_mut(self.buffer.as_mut().unwrap())
+                    .expect("copy buffer is shared with another Arc");
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_ref().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use std::async_iter::AsyncIterator;
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll as StdPoll, Wake, Waker};
+
+use futures::task::{self, Task};
+use futures::{Async, Future, Poll};
+
+use tokio_io::AsyncWrite;
+
+/// Wakes the futures 0.1 task which polled the iterator.
+struct TaskWaker(Task);
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.notify();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.notify();
+    }
+}
+
+/// A future which writes all chunks of an `AsyncIterator` into a writer.
+///
+/// Created by the [`copy_from_async_iter`] function.
+///
+/// [`copy_from_async_iter`]: fn.copy_from_async_iter.html
+#[derive(Debug)]
+pub struct CopyFromAsyncIter<I, W> {
+    iter: Option<I>,
+    read_done: bool,
+    writer: Option<W>,
+    chunk: Option<Box<[u8]>>,
+    pos: usize,
+    amt: u64,
+}
+
+/// Creates a future which takes chunks from `iter` and writes each of them to
+/// `writer`. No separate buffer is needed as chunks own their data.
+///
+/// An error from the iterator fails the copy. On success the number of bytes
+/// written, the iterator and the `writer` are returned.
+///
+/// Available with the `async_iterator` feature, which needs a nightly compiler.
+pub fn copy_from_async_iter<I, W>(iter: I, writer: W) -> CopyFromAsyncIter<I, W>
+    where I: AsyncIterator<Item = io::Result<Box<[u8]>>> + Unpin,
+          W: AsyncWrite,
+{
+    CopyFromAsyncIter {
+        iter: Some(iter),
+        read_done: false,
+        writer: Some(writer),
+        chunk: None,
+        pos: 0,
+        amt: 0,
+    }
+}
+
+impl<I, W> Future for CopyFromAsyncIter<I, W>
+    where I: AsyncIterator<Item = io::Result<Box<[u8]>>> + Unpin,
+          W: AsyncWrite,
+{
+    type Item = (u64, I, W);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, I, W), io::Error> {
+        loop {
+            // If we have no chunk to write, get the next one.
+            if self.chunk.is_none() && !self.read_done {
+                let waker = Waker::from(Arc::new(TaskWaker(task::current())));
+                let mut cx = Context::from_waker(&waker);
+                let iter = self.iter.as_mut().unwrap();
+                match Pin::new(iter).poll_next(&mut cx) {
+                    StdPoll::Ready(Some(chunk)) => {
+                        self.chunk = Some(chunk?);
+                        self.pos = 0;
+                    }
+                    StdPoll::Ready(None) => self.read_done = true,
+                    StdPoll::Pending => return Ok(Async::NotReady),
+                }
+            }
+
+            // If we have a chunk, let's write it out!
+            if let Some(ref chunk) = self.chunk {
+                let writer = self.writer.as_mut().unwrap();
+                while self.pos < chunk.len() {
+                    let i = try_nb!(writer.write(&chunk[self.pos..]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    } else {
+                        self.pos += i;
+                        self.amt += i as u64;
+                    }
+                }
+            }
+            self.chunk = None;
+
+            // If we've written all the chunks and the iterator has ended,
+            // flush out the data and finish the transfer.
+            if self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let iter = self.iter.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                return Ok((self.amt, iter, writer).into())
+            }
+        }
+    }
+}
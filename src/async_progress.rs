@@ -0,0 +1,130 @@
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and drives an asynchronous
+/// progress callback after each write.
+///
+/// Created by the [`copy_with_async_progress`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_async_progress`]: fn.copy_with_async_progress.html
+pub struct CopyAsyncProgress<R, W, F, Fut> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    on_progress: F,
+    progress_fut: Option<Fut>,
+}
+
+/// Variant of [`copy_with_buffer`] which calls `on_progress` with the total
+/// number of bytes written so far after each successful write, and drives the
+/// returned future to completion before copying further.
+///
+/// This allows progress reporting which itself does I/O. If the progress
+/// future fails, the copy fails with that error.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_async_progress<R, W, F, Fut>(reader: R, writer: W, buffer: Box<[u8]>,
+                                              on_progress: F)
+    -> CopyAsyncProgress<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(u64) -> Fut,
+          Fut: Future<Item = (), Error = io::Error>,
+{
+    CopyAsyncProgress {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        on_progress,
+        progress_fut: None,
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F, Fut> fmt::Debug for CopyAsyncProgress<R, W, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyAsyncProgress")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .field("progress_pending", &self.progress_fut.is_some())
+            .finish()
+    }
+}
+
+impl<R, W, F, Fut> Future for CopyAsyncProgress<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(u64) -> Fut,
+          Fut: Future<Item = (), Error = io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // Finish reporting the last write before doing anything else.
+            if let Some(ref mut fut) = self.progress_fut {
+                try_ready!(fut.poll());
+            }
+            self.progress_fut = None;
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out! Each write
+            // is reported before the next one.
+            if self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                    self.progress_fut = Some((self.on_progress)(self.amt));
+                    continue;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+
+use bytes::Bytes;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], passing each chunk through an
+/// asynchronous transform.
+///
+/// Created by the [`copy_async_transform`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_async_transform`]: fn.copy_async_transform.html
+pub struct CopyAsyncTransform<R, W, F, Fut> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    read_buf: Option<Box<[u8]>>,
+    write_buf: Option<Box<[u8]>>,
+    transform: Option<F>,
+    transform_fut: Option<Fut>,
+    output: Bytes,
+}
+
+/// Variant of [`copy_with_buffer`] for transforms which need I/O themselves,
+/// e.g. a remote compression service.
+///
+/// Each chunk read into `read_buf` is passed to `transform` as `Bytes`, the
+/// returned future is driven to completion, and the resulting bytes are written
+/// out through `write_buf`. Only one chunk is in flight at a time. If the
+/// transform fails, the copy fails with that error.
+///
+/// On success the number of bytes written (after the transform) is returned,
+/// along with both buffers and `transform`.
+///
+/// # Panics
+///
+/// Panics if `write_buf` is empty.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_async_transform<R, W, F, Fut>(reader: R, writer: W, read_buf: Box<[u8]>,
+                                          write_buf: Box<[u8]>, transform: F)
+    -> CopyAsyncTransform<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(Bytes) -> Fut,
+          Fut: Future<Item = Bytes, Error = io::Error>,
+{
+    assert!(!write_buf.is_empty(), "write buffer must not be empty");
+    CopyAsyncTransform {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        read_buf: Some(read_buf),
+        write_buf: Some(write_buf),
+        transform: Some(transform),
+        transform_fut: None,
+        output: Bytes::new(),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F, Fut> fmt::Debug for CopyAsyncTransform<R, W, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyAsyncTransform")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("read_buf", &self.read_buf)
+            .field("write_buf", &self.write_buf)
+            .field("transform_pending", &self.transform_fut.is_some())
+            .field("output", &self.output)
+            .finish()
+    }
+}
+
+impl<R, W, F, Fut> Future for CopyAsyncTransform<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(Bytes) -> Fut,
+          Fut: Future<Item = Bytes, Error = io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>, Box<[u8]>, F);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, Box<[u8]>, F), io::Error> {
+        loop {
+            // If our write buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.write_buf.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // Move the next part of the transformed data to the write buffer.
+            if !self.output.is_empty() {
+                let buf = self.write_buf.as_mut().unwrap();
+                let len = cmp::min(buf.len(), self.output.len());
+                buf[..len].copy_from_slice(&self.output.split_to(len));
+                self.pos = 0;
+                self.cap = len;
+                continue;
+            }
+
+            // Wait for the transform of the last chunk.
+            if let Some(ref mut fut) = self.transform_fut {
+                self.output = try_ready!(fut.poll());
+            }
+            if self.transform_fut.take().is_some() {
+                continue;
+            }
+
+            // If everything is written, then we need to read some data to
+            // continue, and start transforming it.
+            if !self.read_done {
+                let buf = self.read_buf.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    let chunk = Bytes::from(&buf[..n]);
+                    self.transform_fut = Some((self.transform.as_mut().unwrap())(chunk));
+                }
+                continue;
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            try_nb!(self.writer.as_mut().unwrap().flush());
+            let reader = self.reader.take().unwrap();
+            let writer = self.writer.take().unwrap();
+            let read_buf = self.read_buf.take().unwrap();
+            let write_buf = self.write_buf.take().unwrap();
+            let transform = self.transform.take().unwrap();
+            return Ok((self.amt, reader, writer, read_buf, write_buf, transform).into())
+        }
+    }
+}
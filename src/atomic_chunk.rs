@@ -0,0 +1,192 @@
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], writing each chunk out in full
+/// before doing anything else.
+///
+/// Created by the [`copy_atomic_chunk_write`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_atomic_chunk_write`]: fn.copy_atomic_chunk_write.html
+#[derive(Debug)]
+pub struct CopyAtomicChunk<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] with a strict contract for chunk
+/// boundaries: each chunk returned by a read is written out completely, like
+/// with `write_all`, before the next read, and any write returning 0 within a
+/// chunk fails the copy with `WriteZero`.
+///
+/// For normal writers this behaves exactly like [`copy_with_buffer`]; it is
+/// meant for code relying on reads in the reader mapping to contiguous runs
+/// of writes in the writer.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_atomic_chunk_write<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> CopyAtomicChunk<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyAtomicChunk {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W: AsyncWrite> CopyAtomicChunk<R, W> {
+    /// Writes the rest of the current chunk, like `write_all`.
+    fn poll_write_chunk(&mut self) -> Poll<(), io::Error> {
+        let buf = self.buffer.as_ref().unwrap();
+        let writer = self.writer.as_mut().unwrap();
+        while self.pos < self.cap {
+            let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+            if i == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                          "write zero byte into writer in the middle of a chunk"));
+            }
+            self.pos += i;
+            self.amt += i as u64;
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<R, W> Future for CopyAtomicChunk<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write all of it out!
+            try_ready!(self.poll_write_chunk());
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::cmp;
+    use std::io::{self, Cursor, Read};
+    use std::rc::Rc;
+
+    use futures::task;
+
+    use super::*;
+
+    /// Counts the bytes handed out.
+    #[derive(Debug)]
+    struct Counted {
+        inner: Cursor<Vec<u8>>,
+        read: Rc<Cell<usize>>,
+    }
+
+    impl Read for Counted {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.read.set(self.read.get() + n);
+            Ok(n)
+        }
+    }
+
+    impl AsyncRead for Counted {}
+
+    /// Takes `max` bytes per write, not being ready every other time, and
+    /// checks that nothing more was read than written so far plus a chunk.
+    #[derive(Debug)]
+    struct Short {
+        out: Vec<u8>,
+        max: usize,
+        ready: bool,
+        read: Rc<Cell<usize>>,
+        chunk: usize,
+    }
+
+    impl io::Write for Short {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            assert!(self.read.get() <= self.out.len() + self.chunk, "read ahead of writes");
+            self.ready = !self.ready;
+            if !self.ready {
+                task::current().notify();
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let n = cmp::min(buf.len(), self.max);
+            self.out.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Short {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    fn copy(max: usize) -> io::Result<(u64, Vec<u8>)> {
+        let read = Rc::new(Cell::new(0));
+        let reader = Counted { inner: Cursor::new((0..50).collect()), read: read.clone() };
+        let writer = Short { out: Vec::new(), max, ready: false, read, chunk: 8 };
+        copy_atomic_chunk_write(reader, writer, vec![0; 8].into_boxed_slice())
+            .wait()
+            .map(|(amt, _, writer, _)| (amt, writer.out))
+    }
+
+    #[test]
+    fn chunks_written_in_full_across_short_writes() {
+        let (amt, out) = copy(3).unwrap();
+        assert_eq!(amt, 50);
+        assert_eq!(out, (0..50).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn zero_write_in_chunk() {
+        let e = copy(0).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::WriteZero);
+    }
+}
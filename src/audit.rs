@@ -0,0 +1,136 @@
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use crc32::crc32;
+
+const RECORD_LEN: usize = 16;
+
+/// A future which copies data like [`Copy`] and writes an audit record for
+/// every chunk to a separate writer.
+///
+/// Created by the [`copy_with_audit`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_audit`]: fn.copy_with_audit.html
+#[derive(Debug)]
+pub struct CopyAudit<R, W, A> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    audit_writer: Option<A>,
+    record: [u8; RECORD_LEN],
+    record_pos: usize,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] keeping an audit trail in `audit_writer`.
+///
+/// After each read, and before the chunk is written to `writer`, a 16-byte
+/// record is written to `audit_writer`: the time in nanoseconds since the Unix
+/// epoch as `u64`, the chunk length as `u32` and its CRC-32 as `u32`, all
+/// big-endian, followed by 4 zero bytes. Both writers are flushed at the end,
+/// and errors of either one fail the copy.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_audit<R, W, A>(reader: R, writer: W, audit_writer: A, buffer: Box<[u8]>)
+    -> CopyAudit<R, W, A>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          A: AsyncWrite,
+{
+    CopyAudit {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        audit_writer: Some(audit_writer),
+        record: [0; RECORD_LEN],
+        record_pos: RECORD_LEN,
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+fn make_record(chunk: &[u8]) -> [u8; RECORD_LEN] {
+    let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+    let ts = if ts > u64::MAX as u128 { u64::MAX } else { ts as u64 };
+    let mut record = [0; RECORD_LEN];
+    record[..8].copy_from_slice(&ts.to_be_bytes());
+    record[8..12].copy_from_slice(&(chunk.len() as u32).to_be_bytes());
+    record[12..16].copy_from_slice(&crc32(chunk).to_be_bytes());
+    record
+}
+
+impl<R, W, A> Future for CopyAudit<R, W, A>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          A: AsyncWrite,
+{
+    type Item = (u64, R, W, A, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, A, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, and make an audit record for it.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.record = make_record(&buf[..n]);
+                    self.record_pos = 0;
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // The audit record goes out first.
+            while self.record_pos < RECORD_LEN {
+                let audit_writer = self.audit_writer.as_mut().unwrap();
+                let i = try_nb!(audit_writer.write(&self.record[self.record_pos..]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into audit writer"));
+                }
+                self.record_pos += i;
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                try_nb!(self.audit_writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let audit_writer = self.audit_writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, audit_writer, buffer).into())
+            }
+        }
+    }
+}
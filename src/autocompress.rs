@@ -0,0 +1,673 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+#[cfg(feature = "flate2")]
+use std::io::Write;
+
+#[cfg(feature = "flate2")]
+use flate2::write::MultiGzDecoder;
+
+use futures::{Future, Poll};
+
+#[cfg(feature = "lz4")]
+use lz4_flex::block::decompress_into_with_dict;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+#[cfg(feature = "zstd")]
+use zstd::stream::raw::{Decoder as ZstdDecoder, Operation};
+
+const SNIFF_LEN: usize = 8;
+
+/// Compression format detected by [`copy_autocompress`].
+///
+/// [`copy_autocompress`]: fn.copy_autocompress.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DetectedFormat {
+    /// gzip, starting with `1f 8b`.
+    Gzip,
+    /// Zstandard, starting with `28 b5 2f fd`.
+    Zstd,
+    /// LZ4 frame format, starting with `04 22 4d 18`.
+    Lz4,
+    /// Anything else, copied as is.
+    Plain,
+}
+
+fn detect(data: &[u8]) -> DetectedFormat {
+    if data.starts_with(b"\x1f\x8b") {
+        DetectedFormat::Gzip
+    } else if data.starts_with(b"\x28\xb5\x2f\xfd") {
+        DetectedFormat::Zstd
+    } else if data.starts_with(b"\x04\x22\x4d\x18") {
+        DetectedFormat::Lz4
+    } else {
+        DetectedFormat::Plain
+    }
+}
+
+#[cfg(feature = "lz4")]
+const LZ4_WINDOW: usize = 65536;
+
+#[cfg(feature = "lz4")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lz4State {
+    Header,
+    Block,
+    ContentChecksum,
+}
+
+/// Push based decoder for LZ4 frames, as `lz4_flex` only decodes frames from
+/// a blocking reader. Checksums are skipped, not verified.
+#[cfg(feature = "lz4")]
+#[derive(Debug)]
+struct Lz4Decoder {
+    input: Vec<u8>,
+    start: usize,
+    state: Lz4State,
+    flags: u8,
+    block: Vec<u8>,
+    block_pos: usize,
+    block_len: usize,
+    dict: Vec<u8>,
+}
+
+#[cfg(feature = "lz4")]
+impl Lz4Decoder {
+    fn new() -> Lz4Decoder {
+        Lz4Decoder {
+            input: Vec::new(),
+            start: 0,
+            state: Lz4State::Header,
+            flags: 0,
+            block: Vec::new(),
+            block_pos: 0,
+            block_len: 0,
+            dict: Vec::new(),
+        }
+    }
+
+    /// Takes all of `data` and decodes as much as fits into `out`, one block
+    /// at a time.
+    fn decode(&mut self, data: &[u8], out: &mut [u8]) -> io::Result<usize> {
+        if !data.is_empty() {
+            // Drop the input consumed so far only when more arrives, so that
+            // it isn't moved once per block.
+            self.input.drain(..self.start);
+            self.start = 0;
+            self.input.extend_from_slice(data);
+        }
+        let mut written = 0;
+        loop {
+            // Hand out the rest of the last block first.
+            let n = cmp::min(self.block_len - self.block_pos, out.len() - written);
+            out[written..written + n]
+                .copy_from_slice(&self.block[self.block_pos..self.block_pos + n]);
+            self.block_pos += n;
+            written += n;
+            if written == out.len() {
+                return Ok(written);
+            }
+
+            let input = &self.input[self.start..];
+            let used = match self.state {
+                Lz4State::Header => {
+                    if input.len() < 7 {
+                        return Ok(written);
+                    }
+                    if input[..4] != b"\x04\x22\x4d\x18"[..] {
+                        return Err(invalid_lz4("bad frame magic"));
+                    }
+                    let flags = input[4];
+                    if flags >> 6 != 1 {
+                        return Err(invalid_lz4("unsupported frame version"));
+                    }
+                    let block_max = match (input[5] >> 4) & 7 {
+                        4 => 64 << 10,
+                        5 => 256 << 10,
+                        6 => 1 << 20,
+                        7 => 4 << 20,
+                        _ => return Err(invalid_lz4("bad block maximum size")),
+                    };
+                    let len = 7 + if flags & 0x08 != 0 { 8 } else { 0 }
+                                + if flags & 0x01 != 0 { 4 } else { 0 };
+                    if input.len() < len {
+                        return Ok(written);
+                    }
+                    self.flags = flags;
+                    self.block.resize(block_max, 0);
+                    self.dict.clear();
+                    self.state = Lz4State::Block;
+                    len
+                }
+                Lz4State::Block => {
+                    if input.len() < 4 {
+                        return Ok(written);
+                    }
+                    let size = u32::from_le_bytes([input[0], input[1], input[2], input[3]]);
+                    if size == 0 {
+                        self.state = if self.flags & 0x04 != 0 {
+                            Lz4State::ContentChecksum
+                        } else {
+                            Lz4State::Header
+                        };
+                        4
+                    } else {
+                        let len = (size & 0x7FFF_FFFF) as usize;
+                        if len > self.block.len() {
+                            return Err(invalid_lz4("block larger than maximum size"));
+                        }
+                        let total = 4 + len + if self.flags & 0x10 != 0 { 4 } else { 0 };
+                        if input.len() < total {
+                            return Ok(written);
+                        }
+                        let data = &input[4..4 + len];
+                        self.block_len = if size & 0x8000_0000 != 0 {
+                            self.block[..len].copy_from_slice(data);
+                            len
+                        } else {
+                            decompress_into_with_dict(data, &mut self.block, &self.dict)
+                                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?
+                        };
+                        self.block_pos = 0;
+                        // Linked blocks may refer to the previous 64 KiB.
+                        if self.flags & 0x20 == 0 {
+                            self.dict.extend_from_slice(&self.block[..self.block_len]);
+                            if self.dict.len() > LZ4_WINDOW {
+                                let excess = self.dict.len() - LZ4_WINDOW;
+                                self.dict.drain(..excess);
+                            }
+                        }
+                        total
+                    }
+                }
+                Lz4State::ContentChecksum => {
+                    if input.len() < 4 {
+                        return Ok(written);
+                    }
+                    self.state = Lz4State::Header;
+                    4
+                }
+            };
+            self.start += used;
+        }
+    }
+
+    fn finish(&mut self) -> io::Result<()> {
+        if self.state != Lz4State::Header || self.input.len() > self.start {
+            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                      "LZ4 input ends in the middle of a frame"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "lz4")]
+fn invalid_lz4(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid LZ4 frame: {}", msg))
+}
+
+/// Moves decoded bytes from `staged[*pos..]` into `out`.
+#[cfg(feature = "flate2")]
+fn take_staged(staged: &[u8], pos: &mut usize, out: &mut [u8]) -> usize {
+    let n = cmp::min(staged.len() - *pos, out.len());
+    out[..n].copy_from_slice(&staged[*pos..*pos + n]);
+    *pos += n;
+    n
+}
+
+enum Decoder {
+    Plain,
+    #[cfg(feature = "flate2")]
+    Gzip {
+        decoder: Box<MultiGzDecoder<Vec<u8>>>,
+        pos: usize,
+    },
+    #[cfg(feature = "zstd")]
+    Zstd {
+        decoder: ZstdDecoder<'static>,
+        frame_done: bool,
+    },
+    #[cfg(feature = "lz4")]
+    Lz4(Lz4Decoder),
+}
+
+impl Decoder {
+    fn new(format: DetectedFormat) -> io::Result<Decoder> {
+        match format {
+            DetectedFormat::Plain => Ok(Decoder::Plain),
+            #[cfg(feature = "flate2")]
+            DetectedFormat::Gzip => Ok(Decoder::Gzip {
+                decoder: Box::new(MultiGzDecoder::new(Vec::new())),
+                pos: 0,
+            }),
+            #[cfg(feature = "zstd")]
+            DetectedFormat::Zstd => Ok(Decoder::Zstd {
+                decoder: ZstdDecoder::new()?,
+                frame_done: false,
+            }),
+            #[cfg(feature = "lz4")]
+            DetectedFormat::Lz4 => Ok(Decoder::Lz4(Lz4Decoder::new())),
+            #[allow(unreachable_patterns)]
+            other => Err(io::Error::new(io::ErrorKind::Unsupported,
+                                        format!("{:?} decoding is not enabled", other))),
+        }
+    }
+
+    /// Decodes some of `input` into `out`, returning how many bytes of each
+    /// were used. Makes progress unless `input` is empty and nothing decoded
+    /// is left over from earlier input.
+    fn decode(&mut self, input: &[u8], out: &mut [u8]) -> io::Result<(usize, usize)> {
+        match *self {
+            Decoder::Plain => {
+                let n = cmp::min(input.len(), out.len());
+                out[..n].copy_from_slice(&input[..n]);
+                Ok((n, n))
+            }
+            #[cfg(feature = "flate2")]
+            Decoder::Gzip { ref mut decoder, ref mut pos } => {
+                // A single write decodes at most about 32 KiB, which is staged
+                // until it is all taken.
+                let mut used = 0;
+                if *pos == decoder.get_ref().len() && !input.is_empty() {
+                    decoder.get_mut().clear();
+                    *pos = 0;
+                    used = decoder.write(input)?;
+                }
+                Ok((used, take_staged(decoder.get_ref(), pos, out)))
+            }
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd { ref mut decoder, ref mut frame_done } => {
+                if *frame_done {
+                    // Between frames, wait for the next one to begin.
+                    if input.is_empty() {
+                        return Ok((0, 0));
+                    }
+                    decoder.reinit()?;
+                }
+                let status = decoder.run_on_buffers(input, out)?;
+                *frame_done = status.remaining == 0;
+                Ok((status.bytes_read, status.bytes_written))
+            }
+            #[cfg(feature = "lz4")]
+            Decoder::Lz4(ref mut d) => Ok((input.len(), d.decode(input, out)?)),
+        }
+    }
+
+    /// Decodes what is left once the input has ended into `out`, returning
+    /// 0 when everything is out. Fails if the input ends in the middle of the
+    /// compressed data.
+    #[cfg_attr(not(any(feature = "flate2", feature = "zstd", feature = "lz4")),
+               allow(unused_variables))]
+    fn finish(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        match *self {
+            Decoder::Plain => Ok(0),
+            #[cfg(feature = "flate2")]
+            Decoder::Gzip { ref mut decoder, ref mut pos } => {
+                if *pos == decoder.get_ref().len() {
+                    decoder.get_mut().clear();
+                    *pos = 0;
+                    decoder.try_finish()?;
+                }
+                Ok(take_staged(decoder.get_ref(), pos, out))
+            }
+            #[cfg(feature = "zstd")]
+            Decoder::Zstd { ref mut decoder, ref mut frame_done } => {
+                if *frame_done {
+                    return Ok(0);
+                }
+                let status = decoder.run_on_buffers(&[], out)?;
+                *frame_done = status.remaining == 0;
+                if status.bytes_written == 0 && !*frame_done {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              "zstd input ends in the middle of a frame"));
+                }
+                Ok(status.bytes_written)
+            }
+            #[cfg(feature = "lz4")]
+            Decoder::Lz4(ref mut d) => {
+                let n = d.decode(&[], out)?;
+                if n == 0 {
+                    d.finish()?;
+                }
+                Ok(n)
+            }
+        }
+    }
+}
+
+/// A future which copies data like [`Copy`], decompressing it first if it is
+/// compressed.
+///
+/// Created by the [`copy_autocompress`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_autocompress`]: fn.copy_autocompress.html
+pub struct CopyAutoCompress<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    sniffed: usize,
+    format: Option<DetectedFormat>,
+    decoder: Option<Decoder>,
+    in_pos: usize,
+    in_cap: usize,
+    out: Box<[u8]>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which looks at the first 8 bytes of the
+/// input and, if they start with the magic bytes of gzip, Zstandard or the
+/// LZ4 frame format, decompresses everything on the fly. Other input is
+/// copied as is.
+///
+/// Decoding gzip needs the `flate2` feature, Zstandard the `zstd` feature and
+/// LZ4 the `lz4` feature; if the detected format is not enabled, the copy
+/// fails with `Unsupported` before writing anything. Concatenated gzip
+/// members and LZ4 frames are decoded one after the other. LZ4 checksums are
+/// not verified. Input ending in the middle of the compressed data fails
+/// with `UnexpectedEof`, or `InvalidInput` for a truncated gzip member.
+///
+/// The data is decoded into a second buffer of the same size as `buffer`,
+/// at most a buffer's worth at a time. LZ4 blocks are decoded whole, so
+/// up to 4 MiB more is held for them.
+///
+/// On success the number of decompressed bytes written and the detected
+/// format are returned.
+///
+/// # Panics
+///
+/// Panics if the buffer is shorter than 8 bytes.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_autocompress<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyAutoCompress<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(buffer.len() >= SNIFF_LEN, "buffer is too short to detect the format");
+    CopyAutoCompress {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        sniffed: 0,
+        format: None,
+        decoder: None,
+        in_pos: 0,
+        in_cap: 0,
+        out: vec![0; buffer.len()].into_boxed_slice(),
+        pos: 0,
+        cap: 0,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug> fmt::Debug for CopyAutoCompress<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyAutoCompress")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("format", &self.format)
+            .field("in_pos", &self.in_pos)
+            .field("in_cap", &self.in_cap)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W> Future for CopyAutoCompress<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, DetectedFormat, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, DetectedFormat, R, W, Box<[u8]>), io::Error> {
+        // Gather the magic bytes first and pick the decoder.
+        while self.format.is_none() {
+            let buf = self.buffer.as_mut().unwrap();
+            if self.sniffed < SNIFF_LEN && !self.read_done {
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(&mut buf[self.sniffed..SNIFF_LEN]));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.sniffed += n;
+                }
+                continue;
+            }
+            let format = detect(&buf[..self.sniffed]);
+            self.decoder = Some(Decoder::new(format)?);
+            self.format = Some(format);
+            self.in_cap = self.sniffed;
+        }
+
+        loop {
+            // If the decoded data is written, then we need to decode some
+            // more, reading more input once it is used up, or finish decoding
+            // on EOF.
+            if self.pos == self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let decoder = self.decoder.as_mut().unwrap();
+                self.pos = 0;
+                self.cap = 0;
+                if self.read_done {
+                    self.cap = decoder.finish(&mut self.out)?;
+                    if self.cap == 0 {
+                        // We've written all the data and we've seen EOF,
+                        // flush out the data and finish the transfer.
+                        try_nb!(self.writer.as_mut().unwrap().flush());
+                        let format = self.format.unwrap();
+                        let reader = self.reader.take().unwrap();
+                        let writer = self.writer.take().unwrap();
+                        let buffer = self.buffer.take().unwrap();
+                        return Ok((self.amt, format, reader, writer, buffer).into())
+                    }
+                } else {
+                    let (used, n) = decoder.decode(&buf[self.in_pos..self.in_cap],
+                                                   &mut self.out)?;
+                    self.in_pos += used;
+                    self.cap = n;
+                    if used == 0 && n == 0 {
+                        let reader = self.reader.as_mut().unwrap();
+                        let n = try_nb!(reader.read(buf));
+                        if n == 0 {
+                            self.read_done = true;
+                        } else {
+                            self.in_pos = 0;
+                            self.in_cap = n;
+                        }
+                    }
+                    continue;
+                }
+            }
+
+            // If we have decoded data, let's write it out!
+            while self.pos < self.cap {
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&self.out[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+    #[cfg(any(feature = "flate2", feature = "lz4"))]
+    use std::io::Write;
+
+    use futures::Future;
+
+    #[cfg(feature = "flate2")]
+    use flate2::{write::GzEncoder, Compression};
+    #[cfg(feature = "lz4")]
+    use lz4_flex::frame::FrameEncoder;
+
+    use super::*;
+
+    fn decompress(input: Vec<u8>, buffer_len: usize) -> io::Result<(DetectedFormat, Vec<u8>)> {
+        copy_autocompress(Cursor::new(input), Cursor::new(Vec::new()),
+                          vec![0; buffer_len].into_boxed_slice())
+            .wait()
+            .map(|(amt, format, _, out, _)| {
+                assert_eq!(amt, out.get_ref().len() as u64);
+                (format, out.into_inner())
+            })
+    }
+
+    /// A writer which fails if it is ever handed more than `max` bytes.
+    #[cfg(any(feature = "flate2", feature = "zstd", feature = "lz4"))]
+    struct Bounded {
+        out: Vec<u8>,
+        max: usize,
+    }
+
+    #[cfg(any(feature = "flate2", feature = "zstd", feature = "lz4"))]
+    impl io::Write for Bounded {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            assert!(buf.len() <= self.max, "{} bytes decoded at once", buf.len());
+            self.out.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(any(feature = "flate2", feature = "zstd", feature = "lz4"))]
+    impl AsyncWrite for Bounded {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    #[cfg(any(feature = "flate2", feature = "zstd"))]
+    fn decompress_bounded(input: Vec<u8>) -> Vec<u8> {
+        let writer = Bounded { out: Vec::new(), max: 4096 };
+        let (_, _, _, writer, _) = copy_autocompress(Cursor::new(input), writer,
+                                                     vec![0; 4096].into_boxed_slice())
+            .wait()
+            .unwrap();
+        writer.out
+    }
+
+    #[test]
+    fn plain_input_is_copied() {
+        let data = b"not compressed at all".to_vec();
+        assert_eq!(decompress(data.clone(), 8).unwrap(), (DetectedFormat::Plain, data));
+    }
+
+    #[cfg(feature = "flate2")]
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gzip_decoded_a_buffer_at_a_time() {
+        let data = vec![7; 10 << 20];
+        assert_eq!(decompress_bounded(gzip(&data)), data);
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gzip_members_concatenated() {
+        let mut input = gzip(b"first ");
+        input.extend(gzip(b"second"));
+        assert_eq!(decompress(input, 16).unwrap(),
+                   (DetectedFormat::Gzip, b"first second".to_vec()));
+    }
+
+    #[cfg(feature = "flate2")]
+    #[test]
+    fn gzip_truncated() {
+        let mut input = gzip(&[7; 100_000]);
+        let len = input.len() - 6;
+        input.truncate(len);
+        let e = decompress(input, 4096).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_decoded_a_buffer_at_a_time() {
+        let data = vec![7; 10 << 20];
+        let input = zstd::encode_all(&data[..], 3).unwrap();
+        assert_eq!(decompress_bounded(input), data);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_frames_concatenated() {
+        let mut input = zstd::encode_all(&b"first "[..], 3).unwrap();
+        input.extend(zstd::encode_all(&b"second"[..], 3).unwrap());
+        assert_eq!(decompress(input, 16).unwrap(),
+                   (DetectedFormat::Zstd, b"first second".to_vec()));
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_truncated() {
+        let mut input = zstd::encode_all(&[7; 100_000][..], 3).unwrap();
+        let len = input.len() - 3;
+        input.truncate(len);
+        let e = decompress(input, 4096).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_blocks_in_one_chunk() {
+        let mut x = 1u32;
+        let data: Vec<u8> = (0..300_000).map(|_| {
+            x = x.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            (x >> 24) as u8
+        }).collect();
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&data).unwrap();
+        assert_eq!(decompress(encoder.finish().unwrap(), 1 << 20).unwrap(),
+                   (DetectedFormat::Lz4, data));
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_decoded_a_buffer_at_a_time() {
+        let data = vec![7; 1 << 20];
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&data).unwrap();
+        let writer = Bounded { out: Vec::new(), max: 100 };
+        let (_, _, _, writer, _) = copy_autocompress(Cursor::new(encoder.finish().unwrap()),
+                                                     writer,
+                                                     vec![0; 100].into_boxed_slice())
+            .wait()
+            .unwrap();
+        assert_eq!(writer.out, data);
+    }
+
+    #[cfg(feature = "lz4")]
+    #[test]
+    fn lz4_block_too_large() {
+        // 64 KiB blocks, then a block claiming to be 128 KiB.
+        let input = b"\x04\x22\x4d\x18\x60\x40\x82\x00\x00\x02\x00".to_vec();
+        let e = decompress(input, 1 << 20).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
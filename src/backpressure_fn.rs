@@ -0,0 +1,140 @@
+use std::fmt;
+use std::io;
+
+use futures::{task, Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], asking an asynchronous function
+/// whether to pause before each read.
+///
+/// Created by the [`copy_with_backpressure_fn`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_backpressure_fn`]: fn.copy_with_backpressure_fn.html
+pub struct CopyBpFn<R, W, F, Fut> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    should_pause: F,
+    pause_fut: Option<Fut>,
+    read_allowed: bool,
+}
+
+/// Variant of [`copy_with_buffer`] for upstream flow control: before each
+/// read, `should_pause` is called and the returned future is driven to
+/// completion.
+///
+/// If it resolves to `true`, the copy yields to the executor and asks again
+/// on the next poll; if `false`, one chunk is read. The check is not repeated
+/// while that read is pending. If the future fails, the copy fails with that
+/// error.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_backpressure_fn<R, W, F, Fut>(reader: R, writer: W, buffer: Box<[u8]>,
+                                               should_pause: F)
+    -> CopyBpFn<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut() -> Fut,
+          Fut: Future<Item = bool, Error = io::Error>,
+{
+    CopyBpFn {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        should_pause,
+        pause_fut: None,
+        read_allowed: false,
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F, Fut> fmt::Debug for CopyBpFn<R, W, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyBpFn")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .field("check_pending", &self.pause_fut.is_some())
+            .field("read_allowed", &self.read_allowed)
+            .finish()
+    }
+}
+
+impl<R, W, F, Fut> Future for CopyBpFn<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut() -> Fut,
+          Fut: Future<Item = bool, Error = io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, ask whether we may read, then read some
+            // data to continue.
+            if self.pos == self.cap && !self.read_done {
+                if !self.read_allowed {
+                    if self.pause_fut.is_none() {
+                        self.pause_fut = Some((self.should_pause)());
+                    }
+                    let pause = try_ready!(self.pause_fut.as_mut().unwrap().poll());
+                    self.pause_fut = None;
+                    if pause {
+                        task::current().notify();
+                        return Ok(Async::NotReady);
+                    }
+                    self.read_allowed = true;
+                }
+
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                self.read_allowed = false;
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
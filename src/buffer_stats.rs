@@ -0,0 +1,140 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Buffer utilization counters collected by [`copy_with_buffer_stats`].
+///
+/// [`copy_with_buffer_stats`]: fn.copy_with_buffer_stats.html
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BufferStats {
+    /// Number of reads which returned data (the final EOF read is not counted).
+    pub reads: u64,
+    /// Total number of bytes returned by those reads.
+    pub total_bytes_read: u64,
+    /// Number of reads which filled the whole buffer.
+    pub full_reads: u64,
+}
+
+impl BufferStats {
+    /// Average fraction of the buffer filled by a read, from 0.0 to 1.0.
+    ///
+    /// `buf_size` is the length of the buffer used for copying.
+    /// Returns 0.0 if nothing was read.
+    pub fn avg_fill_ratio(&self, buf_size: usize) -> f64 {
+        if self.reads == 0 || buf_size == 0 {
+            return 0.0;
+        }
+        self.total_bytes_read as f64 / (self.reads as f64 * buf_size as f64)
+    }
+}
+
+/// A future which copies data like [`Copy`] and collects [`BufferStats`].
+///
+/// Created by the [`copy_with_buffer_stats`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`BufferStats`]: struct.BufferStats.html
+/// [`copy_with_buffer_stats`]: fn.copy_with_buffer_stats.html
+#[derive(Debug)]
+pub struct CopyBufferStats<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    stats: BufferStats,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which also reports how well the buffer was
+/// utilized, to help choosing the buffer size.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_buffer_stats<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyBufferStats<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyBufferStats {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        stats: BufferStats::default(),
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyBufferStats<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, BufferStats, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, BufferStats, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.stats.reads += 1;
+                    self.stats.total_bytes_read += n as u64;
+                    if n == buf.len() {
+                        self.stats.full_reads += 1;
+                    }
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, self.stats, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fill_ratio_of_huge_counts() {
+        let stats = BufferStats {
+            reads: 1 << 40,
+            total_bytes_read: 1 << 62,
+            full_reads: 0,
+        };
+        assert_eq!(stats.avg_fill_ratio(1 << 23), 0.5);
+    }
+}
@@ -0,0 +1,201 @@
+use std::convert::TryInto;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Size of the values swapped by [`copy_byteswap`].
+///
+/// [`copy_byteswap`]: fn.copy_byteswap.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteSwapSize {
+    /// 2 byte values.
+    U16,
+    /// 4 byte values.
+    U32,
+    /// 8 byte values.
+    U64,
+}
+
+impl ByteSwapSize {
+    /// Size of a value in bytes.
+    pub fn bytes(self) -> usize {
+        match self {
+            ByteSwapSize::U16 => 2,
+            ByteSwapSize::U32 => 4,
+            ByteSwapSize::U64 => 8,
+        }
+    }
+
+    fn swap(self, data: &mut [u8]) {
+        match self {
+            ByteSwapSize::U16 => for word in data.chunks_exact_mut(2) {
+                let value = u16::from_be_bytes(word[..].try_into().unwrap());
+                word.copy_from_slice(&value.to_le_bytes());
+            },
+            ByteSwapSize::U32 => for word in data.chunks_exact_mut(4) {
+                let value = u32::from_be_bytes(word[..].try_into().unwrap());
+                word.copy_from_slice(&value.to_le_bytes());
+            },
+            ByteSwapSize::U64 => for word in data.chunks_exact_mut(8) {
+                let value = u64::from_be_bytes(word[..].try_into().unwrap());
+                word.copy_from_slice(&value.to_le_bytes());
+            },
+        }
+    }
+}
+
+/// A future which copies data like [`Copy`], reversing the byte order of
+/// each value.
+///
+/// Created by the [`copy_byteswap`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_byteswap`]: fn.copy_byteswap.html
+#[derive(Debug)]
+pub struct CopyByteSwap<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    rem: usize,
+    amt: u64,
+    word_size: ByteSwapSize,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] converting big-endian values of
+/// `word_size` to little-endian, or the other way round, in the buffer
+/// before writing it.
+///
+/// Reads may split values anywhere: the bytes of an incomplete value are
+/// kept until the rest of it is read. Fails with `UnexpectedEof` if the
+/// reader ends in the middle of a value.
+///
+/// # Panics
+///
+/// Panics if `buffer` is shorter than a value.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_byteswap<R, W>(reader: R, writer: W, buffer: Box<[u8]>, word_size: ByteSwapSize)
+    -> CopyByteSwap<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(buffer.len() >= word_size.bytes(), "buffer is shorter than a value");
+    CopyByteSwap {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        rem: 0,
+        word_size,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyByteSwap<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, after the bytes of an incomplete value left over, and
+            // swap the whole values in place.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                buf.copy_within(self.cap..self.cap + self.rem, 0);
+                self.pos = 0;
+                self.cap = 0;
+                let n = try_nb!(reader.read(&mut buf[self.rem..]));
+                if n == 0 {
+                    if self.rem != 0 {
+                        return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                  "input ends in the middle of a value"));
+                    }
+                    self.read_done = true;
+                } else {
+                    let len = self.rem + n;
+                    self.cap = len - len % self.word_size.bytes();
+                    self.rem = len - self.cap;
+                    self.word_size.swap(&mut buf[..self.cap]);
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+
+    use futures::Future;
+
+    use tokio_io::AsyncRead;
+
+    use super::*;
+
+    /// Reads at most 3 bytes at a time, splitting values.
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(3);
+            self.0.read(&mut buf[..n])
+        }
+    }
+
+    impl AsyncRead for Trickle {}
+
+    fn swap(input: &[u8]) -> io::Result<Vec<u8>> {
+        copy_byteswap(Trickle(Cursor::new(input.to_vec())), Cursor::new(Vec::new()),
+                      vec![0; 8].into_boxed_slice(), ByteSwapSize::U32)
+            .wait()
+            .map(|(_, _, out, _)| out.into_inner())
+    }
+
+    #[test]
+    fn split_values() {
+        let out = swap(&[1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]).unwrap();
+        assert_eq!(out, [4, 3, 2, 1, 8, 7, 6, 5, 12, 11, 10, 9]);
+    }
+
+    #[test]
+    fn partial_value_at_eof() {
+        let e = swap(&[1, 2, 3, 4, 5, 6]).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
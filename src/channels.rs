@@ -0,0 +1,201 @@
+use std::io;
+
+use futures::{Future, Poll, Stream};
+use futures::sync::mpsc;
+
+use tokio_io::AsyncWrite;
+
+/// Polls `receiver`, whose errors cannot happen.
+fn poll_recv<B>(receiver: &mut mpsc::Receiver<B>) -> Poll<Option<B>, io::Error> {
+    match receiver.poll() {
+        Ok(x) => Ok(x),
+        Err(()) => unreachable!("mpsc receivers do not fail"),
+    }
+}
+
+/// A future which forwards byte chunks from one channel into another.
+///
+/// Created by the [`copy_between_channels`] function.
+///
+/// [`copy_between_channels`]: fn.copy_between_channels.html
+#[derive(Debug)]
+pub struct CopyChannels<B> {
+    receiver: mpsc::Receiver<B>,
+    sender: Option<mpsc::UnboundedSender<B>>,
+    amt: u64,
+}
+
+/// Creates a future which moves every item from `receiver` into `sender`
+/// until all senders of `receiver` are dropped, without copying the bytes.
+///
+/// Fails with `BrokenPipe` if the receiving side of `sender` goes away. On
+/// success the total length of the forwarded items and `sender` are
+/// returned.
+pub fn copy_between_channels<B>(receiver: mpsc::Receiver<B>, sender: mpsc::UnboundedSender<B>)
+    -> CopyChannels<B>
+    where B: AsRef<[u8]>,
+{
+    CopyChannels {
+        receiver,
+        sender: Some(sender),
+        amt: 0,
+    }
+}
+
+impl<B> Future for CopyChannels<B>
+    where B: AsRef<[u8]>,
+{
+    type Item = (u64, mpsc::UnboundedSender<B>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, mpsc::UnboundedSender<B>), io::Error> {
+        loop {
+            match try_ready!(poll_recv(&mut self.receiver)) {
+                Some(item) => {
+                    let len = item.as_ref().len() as u64;
+                    if self.sender.as_ref().unwrap().unbounded_send(item).is_err() {
+                        return Err(io::Error::new(io::ErrorKind::BrokenPipe,
+                                                  "receiving channel is closed"));
+                    }
+                    self.amt += len;
+                }
+                None => return Ok((self.amt, self.sender.take().unwrap()).into()),
+            }
+        }
+    }
+}
+
+/// A future which writes byte chunks from a channel into a writer.
+///
+/// Created by the [`copy_mpsc_to_async_write`] function.
+///
+/// [`copy_mpsc_to_async_write`]: fn.copy_mpsc_to_async_write.html
+#[derive(Debug)]
+pub struct CopyMpscToWrite<B, W> {
+    receiver: mpsc::Receiver<B>,
+    read_done: bool,
+    writer: Option<W>,
+    item: Option<B>,
+    pos: usize,
+    amt: u64,
+}
+
+/// Creates a future which writes every item from `receiver` into `writer`
+/// and flushes it once all senders are dropped.
+///
+/// Each item is written directly, so no copy buffer is needed. On success
+/// the number of bytes written and `writer` are returned.
+pub fn copy_mpsc_to_async_write<B, W>(receiver: mpsc::Receiver<B>, writer: W)
+    -> CopyMpscToWrite<B, W>
+    where B: AsRef<[u8]>,
+          W: AsyncWrite,
+{
+    CopyMpscToWrite {
+        receiver,
+        read_done: false,
+        writer: Some(writer),
+        item: None,
+        pos: 0,
+        amt: 0,
+    }
+}
+
+impl<B, W> Future for CopyMpscToWrite<B, W>
+    where B: AsRef<[u8]>,
+          W: AsyncWrite,
+{
+    type Item = (u64, W);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, W), io::Error> {
+        loop {
+            // If the last item is written, then we need to receive another
+            // one to continue.
+            if self.item.is_none() && !self.read_done {
+                match try_ready!(poll_recv(&mut self.receiver)) {
+                    Some(item) => {
+                        self.item = Some(item);
+                        self.pos = 0;
+                    }
+                    None => self.read_done = true,
+                }
+            }
+
+            // If we have an item, let's write it out!
+            if let Some(ref item) = self.item {
+                let data = item.as_ref();
+                while self.pos < data.len() {
+                    let writer = self.writer.as_mut().unwrap();
+                    let i = try_nb!(writer.write(&data[self.pos..]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    } else {
+                        self.pos += i;
+                        self.amt += i as u64;
+                    }
+                }
+            }
+            self.item = None;
+
+            // If we've written all the data and all senders are gone, flush
+            // out the data and finish the transfer.
+            if self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let writer = self.writer.take().unwrap();
+                return Ok((self.amt, writer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::thread;
+
+    use futures::Sink;
+
+    use super::*;
+
+    fn feed(items: Vec<&'static [u8]>) -> mpsc::Receiver<&'static [u8]> {
+        let (sender, receiver) = mpsc::channel(1);
+        thread::spawn(move || {
+            let mut sender = sender;
+            for item in items {
+                sender = sender.send(item).wait().unwrap();
+            }
+        });
+        receiver
+    }
+
+    #[test]
+    fn forwards_between_channels() {
+        let (sender, receiver) = mpsc::unbounded();
+        let (amt, sender) = copy_between_channels(feed(vec![b"ab", b"", b"cde"]), sender)
+            .wait()
+            .unwrap();
+        drop(sender);
+        assert_eq!(amt, 5);
+        let items: Vec<_> = receiver.collect().wait().unwrap();
+        assert_eq!(items, [&b"ab"[..], b"", b"cde"]);
+    }
+
+    #[test]
+    fn closed_receiving_channel() {
+        let (sender, receiver) = mpsc::unbounded();
+        drop(receiver);
+        let e = copy_between_channels(feed(vec![b"ab"]), sender).wait().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn writes_items() {
+        let (amt, writer) = copy_mpsc_to_async_write(feed(vec![b"ab", b"", b"cde"]),
+                                                     Cursor::new(Vec::new()))
+            .wait()
+            .unwrap();
+        assert_eq!(amt, 5);
+        assert_eq!(writer.into_inner(), b"abcde");
+    }
+}
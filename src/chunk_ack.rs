@@ -0,0 +1,133 @@
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], waiting for an acknowledgement
+/// after each chunk.
+///
+/// Created by the [`copy_with_chunk_ack`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_chunk_ack`]: fn.copy_with_chunk_ack.html
+pub struct CopyChunkAck<R, W, F, Fut> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    ack: Option<F>,
+    ack_fut: Option<Fut>,
+}
+
+/// Variant of [`copy_with_buffer`] for protocols which need the peer to
+/// acknowledge each chunk before the next one is sent, like SFTP.
+///
+/// Once a chunk is completely written, `ack` is called with the total number
+/// of bytes written so far and the returned future is driven to completion
+/// before the next read. If it fails, the copy fails with that error. `ack`
+/// is returned on success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_chunk_ack<R, W, F, Fut>(reader: R, writer: W, buffer: Box<[u8]>, ack: F)
+    -> CopyChunkAck<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(u64) -> Fut,
+          Fut: Future<Item = (), Error = io::Error>,
+{
+    CopyChunkAck {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        ack: Some(ack),
+        ack_fut: None,
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F, Fut> fmt::Debug for CopyChunkAck<R, W, F, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyChunkAck")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .field("ack_pending", &self.ack_fut.is_some())
+            .finish()
+    }
+}
+
+impl<R, W, F, Fut> Future for CopyChunkAck<R, W, F, Fut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(u64) -> Fut,
+          Fut: Future<Item = (), Error = io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>, F);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, F), io::Error> {
+        loop {
+            // Wait for the last chunk to be acknowledged.
+            if let Some(ref mut fut) = self.ack_fut {
+                try_ready!(fut.poll());
+            }
+            self.ack_fut = None;
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out and ask for an
+            // acknowledgement!
+            if self.pos < self.cap {
+                while self.pos < self.cap {
+                    let buf = self.buffer.as_mut().unwrap();
+                    let writer = self.writer.as_mut().unwrap();
+                    let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    } else {
+                        self.pos += i;
+                        self.amt += i as u64;
+                    }
+                }
+                self.ack_fut = Some((self.ack.as_mut().unwrap())(self.amt));
+                continue;
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let ack = self.ack.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, ack).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+
+/// A future which copies data like [`Copy`], but fails if reading and writing
+/// out a single chunk takes too long.
+///
+/// Created by the [`copy_per_chunk_deadline`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_per_chunk_deadline`]: fn.copy_per_chunk_deadline.html
+#[derive(Debug)]
+pub struct CopyChunkDeadline<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    chunk_deadline: Duration,
+    chunk_started: bool,
+    sleep: Delay,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which gives each chunk `chunk_deadline` to
+/// be read and fully written out. If a chunk is not done in time, the copy
+/// fails with `TimedOut`.
+///
+/// This is stricter than a timeout for the whole transfer and catches slow
+/// peers one chunk at a time. The final flush belongs to the last chunk.
+/// Needs to be run within a `tokio-timer` timer context.
+///
+/// Available with the `tokio-timer` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_per_chunk_deadline<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                     chunk_deadline: Duration) -> CopyChunkDeadline<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyChunkDeadline {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        chunk_deadline,
+        chunk_started: false,
+        sleep: Delay::new(Instant::now() + chunk_deadline),
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyChunkDeadline<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // A new chunk gets a fresh deadline.
+            if self.pos == self.cap && !self.read_done && !self.chunk_started {
+                self.sleep.reset(Instant::now() + self.chunk_deadline);
+                self.chunk_started = true;
+            }
+            match self.sleep.poll() {
+                Ok(Async::Ready(())) => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                              "chunk deadline elapsed"));
+                }
+                Ok(Async::NotReady) => {}
+                Err(e) => return Err(io::Error::other(e)),
+            }
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+            self.chunk_started = false;
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
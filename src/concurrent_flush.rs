@@ -0,0 +1,148 @@
+use std::io;
+use std::mem;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], periodically flushing the writer
+/// while already reading the next chunk.
+///
+/// Created by the [`copy_with_concurrent_flush`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_concurrent_flush`]: fn.copy_with_concurrent_flush.html
+#[derive(Debug)]
+pub struct CopyConcurrentFlush<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    secondary_cap: usize,
+    amt: u64,
+    flush_every: u64,
+    since_flush: u64,
+    flushing: bool,
+    buffer: Option<Box<[u8]>>,
+    secondary: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which flushes the writer after each
+/// `flush_every` bytes, without stalling the reading side.
+///
+/// While a flush is in progress, the next chunk is read into the `secondary`
+/// buffer. Once the flush has completed, the buffers are swapped and writing
+/// continues. This helps with writers which take long to flush.
+/// `flush_every` of 0 means flushing only at the end, like [`copy_with_buffer`].
+///
+/// On success both buffers are returned, in no particular order.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_concurrent_flush<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                        secondary: Box<[u8]>, flush_every: u64)
+    -> CopyConcurrentFlush<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyConcurrentFlush {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        secondary_cap: 0,
+        flush_every,
+        since_flush: 0,
+        flushing: false,
+        buffer: Some(buffer),
+        secondary: Some(secondary),
+    }
+}
+
+impl<R, W> Future for CopyConcurrentFlush<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, Box<[u8]>), io::Error> {
+        loop {
+            // While flushing, fill the secondary buffer as far as the reader
+            // lets us without waiting for it.
+            if self.flushing {
+                if self.secondary_cap == 0 && !self.read_done {
+                    let buf = self.secondary.as_mut().unwrap();
+                    let reader = self.reader.as_mut().unwrap();
+                    match reader.read(buf) {
+                        Ok(0) => self.read_done = true,
+                        Ok(n) => self.secondary_cap = n,
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                match self.writer.as_mut().unwrap().flush() {
+                    Ok(()) => self.flushing = false,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+
+            // If our buffer is empty, take over what was read during the
+            // flush, or read some data to continue.
+            if self.pos == self.cap {
+                if self.secondary_cap > 0 {
+                    mem::swap(&mut self.buffer, &mut self.secondary);
+                    self.pos = 0;
+                    self.cap = self.secondary_cap;
+                    self.secondary_cap = 0;
+                } else if !self.read_done {
+                    let buf = self.buffer.as_mut().unwrap();
+                    let reader = self.reader.as_mut().unwrap();
+                    let n = try_nb!(reader.read(buf));
+                    if n == 0 {
+                        self.read_done = true;
+                    } else {
+                        self.pos = 0;
+                        self.cap = n;
+                    }
+                }
+            }
+
+            // If our buffer has some data, let's write it out, stopping to
+            // flush when it's time.
+            while self.pos < self.cap && !self.flushing {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                    self.since_flush += i as u64;
+                    if self.flush_every != 0 && self.since_flush >= self.flush_every {
+                        self.since_flush = 0;
+                        self.flushing = true;
+                    }
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if !self.flushing && self.pos == self.cap && self.secondary_cap == 0 && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let secondary = self.secondary.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, secondary).into())
+            }
+        }
+    }
+}
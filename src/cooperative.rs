@@ -0,0 +1,109 @@
+use std::cmp;
+use std::io;
+
+use futures::{task, Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], but yields back to the executor
+/// after reading a certain amount of bytes in a single `poll`.
+///
+/// Created by the [`copy_cooperative`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_cooperative`]: fn.copy_cooperative.html
+#[derive(Debug)]
+pub struct CopyCooperative<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    bytes_per_poll: usize,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which reads at most `bytes_per_poll` bytes
+/// in a single `poll` invocation. Once the limit is reached, the current task is
+/// notified and the future returns `NotReady`, letting other futures in a
+/// single-threaded executor make progress.
+///
+/// This is about scheduler fairness, not about limiting throughput.
+/// `bytes_per_poll` of 0 means the buffer length, i.e. at most one read per poll.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_cooperative<R, W>(reader: R, writer: W, buffer: Box<[u8]>, bytes_per_poll: usize)
+    -> CopyCooperative<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    let bytes_per_poll = if bytes_per_poll == 0 { buffer.len() } else { bytes_per_poll };
+    CopyCooperative {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        bytes_per_poll,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyCooperative<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        let mut read_this_poll = 0;
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue. Yield first if we have already read enough this time.
+            if self.pos == self.cap && !self.read_done {
+                if read_this_poll >= self.bytes_per_poll {
+                    task::current().notify();
+                    return Ok(Async::NotReady);
+                }
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let len = cmp::min(buf.len(), self.bytes_per_poll - read_this_poll);
+                let n = try_nb!(reader.read(&mut buf[..len]));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                    read_this_poll += n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,327 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use crc32::crc32;
+
+const HEADER_LEN: usize = 8;
+
+/// A future which copies data from a reader into a writer, prefixing each
+/// chunk with its length and CRC-32.
+///
+/// Created by the [`copy_framed_crc32`] function.
+///
+/// [`copy_framed_crc32`]: fn.copy_framed_crc32.html
+#[derive(Debug)]
+pub struct CopyFramedCrc32<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    header: [u8; HEADER_LEN],
+    header_pos: usize,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] framing each chunk read as
+/// `[u32 length][u32 crc32][data]`, both numbers big-endian.
+///
+/// [`copy_unframe_crc32`] does the reverse. The number of data bytes, headers
+/// excluded, is returned on success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`copy_unframe_crc32`]: fn.copy_unframe_crc32.html
+pub fn copy_framed_crc32<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyFramedCrc32<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyFramedCrc32 {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        header: [0; HEADER_LEN],
+        header_pos: HEADER_LEN,
+        pos: 0,
+        cap: 0,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyFramedCrc32<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, and make a header for it.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else if n > u32::MAX as usize {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "chunk too large"));
+                } else {
+                    self.header[..4].copy_from_slice(&(n as u32).to_be_bytes());
+                    self.header[4..].copy_from_slice(&crc32(&buf[..n]).to_be_bytes());
+                    self.header_pos = 0;
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // Write out the header first, then the data.
+            while self.header_pos < HEADER_LEN {
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&self.header[self.header_pos..]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                }
+                self.header_pos += i;
+            }
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+/// A future which reads chunks framed by [`copy_framed_crc32`], checks them
+/// and writes the data into a writer.
+///
+/// Created by the [`copy_unframe_crc32`] function.
+///
+/// [`copy_framed_crc32`]: fn.copy_framed_crc32.html
+/// [`copy_unframe_crc32`]: fn.copy_unframe_crc32.html
+#[derive(Debug)]
+pub struct CopyUnframeCrc32<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    header: [u8; HEADER_LEN],
+    header_pos: usize,
+    filled: usize,
+    pos: usize,
+    cap: usize,
+    len: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Reverse of [`copy_framed_crc32`]: reads each header and the data after it
+/// into `buffer`, verifies the CRC-32 and writes the data out.
+///
+/// Fails with `InvalidData` on a CRC mismatch or if a chunk does not fit into
+/// `buffer`, and with `UnexpectedEof` if the reader ends in the middle of a
+/// frame. A chunk is not written out before it's verified.
+///
+/// [`copy_framed_crc32`]: fn.copy_framed_crc32.html
+pub fn copy_unframe_crc32<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> CopyUnframeCrc32<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyUnframeCrc32 {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        header: [0; HEADER_LEN],
+        header_pos: 0,
+        filled: 0,
+        pos: 0,
+        cap: 0,
+        len: 0,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyUnframeCrc32<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, read the next header, then the data.
+            if self.pos == self.cap && !self.read_done {
+                let reader = self.reader.as_mut().unwrap();
+                while self.header_pos < HEADER_LEN {
+                    let n = try_nb!(reader.read(&mut self.header[self.header_pos..]));
+                    if n == 0 {
+                        if self.header_pos != 0 {
+                            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                      "reader ended in a frame header"));
+                        }
+                        self.read_done = true;
+                        break;
+                    }
+                    self.header_pos += n;
+                    if self.header_pos == HEADER_LEN {
+                        let h = self.header;
+                        self.len = u32::from_be_bytes([h[0], h[1], h[2], h[3]]) as usize;
+                        if self.len > self.buffer.as_ref().unwrap().len() {
+                            return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                      "frame larger than the buffer"));
+                        }
+                        self.filled = 0;
+                    }
+                }
+
+                // Nothing is exposed for writing until the whole chunk is in
+                // and has passed the check.
+                if !self.read_done {
+                    let buf = self.buffer.as_mut().unwrap();
+                    while self.filled < self.len {
+                        let n = try_nb!(reader.read(&mut buf[self.filled..self.len]));
+                        if n == 0 {
+                            return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                                      "reader ended in frame data"));
+                        }
+                        self.filled += n;
+                    }
+                    let h = self.header;
+                    if crc32(&buf[..self.len]) != u32::from_be_bytes([h[4], h[5], h[6], h[7]]) {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "CRC-32 mismatch"));
+                    }
+                    self.header_pos = 0;
+                    self.pos = 0;
+                    self.cap = self.len;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io::{self, Cursor, Read};
+
+    use futures::{Async, Future};
+
+    use tokio_io::AsyncRead;
+
+    use super::*;
+
+    /// Hands out the queued chunks, with `None` standing for `WouldBlock`.
+    struct Chunks(VecDeque<Option<Vec<u8>>>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(Some(mut chunk)) => {
+                    let n = chunk.len().min(buf.len());
+                    buf[..n].copy_from_slice(&chunk[..n]);
+                    if n < chunk.len() {
+                        self.0.push_front(Some(chunk.split_off(n)));
+                    }
+                    Ok(n)
+                }
+                Some(None) => Err(io::ErrorKind::WouldBlock.into()),
+                None => Ok(0),
+            }
+        }
+    }
+
+    impl AsyncRead for Chunks {}
+
+    fn frame(data: &[u8], crc: u32) -> Vec<u8> {
+        let mut v = Vec::new();
+        v.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        v.extend_from_slice(&crc.to_be_bytes());
+        v.extend_from_slice(data);
+        v
+    }
+
+    fn split_frame(data: &[u8], crc: u32) -> Chunks {
+        let f = frame(data, crc);
+        let mid = HEADER_LEN + data.len() / 2;
+        Chunks(vec![Some(f[..mid].to_vec()), None, Some(f[mid..].to_vec())].into())
+    }
+
+    #[test]
+    fn would_block_mid_frame() {
+        let data = b"hello, framed world";
+        let mut out = Cursor::new(Vec::new());
+        let mut copy = copy_unframe_crc32(split_frame(data, crc32(data)), &mut out,
+                                          vec![0; 64].into_boxed_slice());
+        assert!(copy.poll().unwrap().is_not_ready());
+        match copy.poll().unwrap() {
+            Async::Ready((n, _, _, _)) => assert_eq!(n, data.len() as u64),
+            Async::NotReady => panic!("copy did not finish"),
+        }
+        drop(copy);
+        assert_eq!(out.get_ref(), data);
+    }
+
+    #[test]
+    fn nothing_written_before_check() {
+        let data = b"hello, framed world";
+        let mut out = Cursor::new(Vec::new());
+        let mut copy = copy_unframe_crc32(split_frame(data, !crc32(data)), &mut out,
+                                          vec![0; 64].into_boxed_slice());
+        assert!(copy.poll().unwrap().is_not_ready());
+        let e = copy.poll().err().expect("bad CRC was accepted");
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        drop(copy);
+        assert!(out.get_ref().is_empty());
+    }
+}
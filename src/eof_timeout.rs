@@ -0,0 +1,116 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+
+/// A future which copies data like [`Copy`], but fails if the reader does not
+/// reach EOF in time.
+///
+/// Created by the [`copy_with_eof_timeout`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_eof_timeout`]: fn.copy_with_eof_timeout.html
+#[derive(Debug)]
+pub struct CopyEofTimeout<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    deadline: Delay,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for protocols with a maximum transaction
+/// duration: if EOF is not read within `eof_timeout` of calling this function,
+/// the copy fails with `TimedOut`.
+///
+/// Unlike [`copy_per_chunk_deadline`], slow chunks are fine as long as the
+/// peer finishes in time. Once EOF is seen, writing out the rest and flushing
+/// are not limited. Needs to be run within a `tokio-timer` timer context.
+///
+/// Available with the `tokio-timer` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`copy_per_chunk_deadline`]: fn.copy_per_chunk_deadline.html
+pub fn copy_with_eof_timeout<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                   eof_timeout: Duration) -> CopyEofTimeout<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyEofTimeout {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        deadline: Delay::new(Instant::now() + eof_timeout),
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyEofTimeout<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            if !self.read_done {
+                match self.deadline.poll() {
+                    Ok(Async::Ready(())) => {
+                        return Err(io::Error::new(io::ErrorKind::TimedOut,
+                                                  "EOF not reached in time"));
+                    }
+                    Ok(Async::NotReady) => {}
+                    Err(e) => return Err(io::Error::other(e)),
+                }
+            }
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
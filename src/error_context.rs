@@ -0,0 +1,158 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Stage of the copy in which an error happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CopyPhase {
+    /// Reading from the reader.
+    Read,
+    /// Writing to the writer.
+    Write,
+    /// Flushing the writer.
+    Flush,
+}
+
+/// Error of [`copy_with_error_context`], telling where and when the copy failed.
+///
+/// [`copy_with_error_context`]: fn.copy_with_error_context.html
+#[derive(Debug)]
+pub struct CopyErrorContext {
+    /// Source file of the failing operation.
+    pub file: &'static str,
+    /// Source line of the failing operation.
+    pub line: u32,
+    /// Which stage of the copy failed.
+    pub phase: CopyPhase,
+    /// Number of bytes successfully written before the error.
+    pub bytes_before: u64,
+    /// The underlying error.
+    pub inner: io::Error,
+}
+
+impl fmt::Display for CopyErrorContext {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?} failed after {} bytes at {}:{}: {}",
+               self.phase, self.bytes_before, self.file, self.line, self.inner)
+    }
+}
+
+impl Error for CopyErrorContext {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.inner)
+    }
+}
+
+impl From<CopyErrorContext> for io::Error {
+    fn from(e: CopyErrorContext) -> io::Error {
+        io::Error::new(e.inner.kind(), e)
+    }
+}
+
+/// A future which copies data like [`Copy`], but its errors carry context.
+///
+/// Created by the [`copy_with_error_context`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_error_context`]: fn.copy_with_error_context.html
+#[derive(Debug)]
+pub struct CopyCtx<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which fails with [`CopyErrorContext`]
+/// instead of a bare `io::Error`. It tells the phase of the copy, the number of
+/// bytes copied before and the source location of the failed operation, which
+/// helps when debugging many concurrent copies.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`CopyErrorContext`]: struct.CopyErrorContext.html
+pub fn copy_with_error_context<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyCtx<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyCtx {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+/// Like `try_nb!`, but wraps errors into `CopyErrorContext`.
+macro_rules! try_ctx {
+    ($e:expr, $phase:expr, $amt:expr) => (match $e {
+        Ok(t) => t,
+        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Async::NotReady),
+        Err(e) => return Err(CopyErrorContext {
+            file: file!(),
+            line: line!(),
+            phase: $phase,
+            bytes_before: $amt,
+            inner: e,
+        }),
+    })
+}
+
+impl<R, W> Future for CopyCtx<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = CopyErrorContext;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), CopyErrorContext> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_ctx!(reader.read(buf), CopyPhase::Read, self.amt);
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_ctx!(writer.write(&buf[self.pos..self.cap]), CopyPhase::Write, self.amt);
+                if i == 0 {
+                    let e = io::Error::new(io::ErrorKind::WriteZero, "write zero byte into writer");
+                    try_ctx!(Err(e), CopyPhase::Write, self.amt);
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_ctx!(self.writer.as_mut().unwrap().flush(), CopyPhase::Flush, self.amt);
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
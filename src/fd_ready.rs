@@ -0,0 +1,72 @@
+//! Waiting for raw file descriptors to become readable or writable, for
+//! futures which call the syscalls on them directly.
+
+use std::io;
+use std::os::unix::io::RawFd;
+
+use libc;
+
+use mio::{self, Evented, PollOpt, Token};
+use mio::unix::EventedFd;
+
+use tokio_reactor::PollEvented;
+
+/// A duplicate of a descriptor, so that it can be registered with the
+/// reactor even if the original one already is.
+#[derive(Debug)]
+struct DupFd(RawFd);
+
+impl Evented for DupFd {
+    fn register(&self, poll: &mio::Poll, token: Token, interest: mio::Ready, opts: PollOpt)
+        -> io::Result<()>
+    {
+        EventedFd(&self.0).register(poll, token, interest, opts)
+    }
+
+    fn reregister(&self, poll: &mio::Poll, token: Token, interest: mio::Ready, opts: PollOpt)
+        -> io::Result<()>
+    {
+        EventedFd(&self.0).reregister(poll, token, interest, opts)
+    }
+
+    fn deregister(&self, poll: &mio::Poll) -> io::Result<()> {
+        EventedFd(&self.0).deregister(poll)
+    }
+}
+
+impl Drop for DupFd {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.0); }
+    }
+}
+
+/// Readiness of a descriptor, registered on the first wait.
+#[derive(Debug, Default)]
+pub struct FdReady {
+    io: Option<PollEvented<DupFd>>,
+}
+
+impl FdReady {
+    fn get(&mut self, fd: RawFd) -> io::Result<&PollEvented<DupFd>> {
+        if self.io.is_none() {
+            let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+            if dup == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            self.io = Some(PollEvented::new(DupFd(dup)));
+        }
+        Ok(self.io.as_ref().unwrap())
+    }
+
+    /// Schedules the current task to be woken up once `fd` becomes readable,
+    /// after an operation on it returned `WouldBlock`.
+    pub fn wait_readable(&mut self, fd: RawFd) -> io::Result<()> {
+        self.get(fd)?.clear_read_ready(mio::Ready::readable())
+    }
+
+    /// Schedules the current task to be woken up once `fd` becomes writable,
+    /// after an operation on it returned `WouldBlock`.
+    pub fn wait_writable(&mut self, fd: RawFd) -> io::Result<()> {
+        self.get(fd)?.clear_write_ready()
+    }
+}
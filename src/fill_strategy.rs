@@ -0,0 +1,237 @@
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Controls how [`copy_with_fill_strategy`] fills the buffer from the reader.
+///
+/// [`copy_with_fill_strategy`]: fn.copy_with_fill_strategy.html
+pub trait FillStrategy<R: AsyncRead> {
+    /// Reads some data from `reader` into `buf` and returns how many bytes
+    /// were filled, 0 meaning EOF.
+    ///
+    /// Returns `NotReady` if the reader is not ready, keeping any partial
+    /// progress to continue on the next call.
+    fn poll_fill(&mut self, reader: &mut R, buf: &mut [u8]) -> Poll<usize, io::Error>;
+}
+
+/// Fills the buffer with whatever a single read returns, like [`copy_with_buffer`].
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+#[derive(Debug, Clone, Copy, Default)]
+pub struct GreedyFill;
+
+impl<R: AsyncRead> FillStrategy<R> for GreedyFill {
+    fn poll_fill(&mut self, reader: &mut R, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        Ok(Async::Ready(try_nb!(reader.read(buf))))
+    }
+}
+
+/// Fills exactly N bytes before handing the chunk over. Only the last chunk
+/// before EOF may be shorter.
+///
+/// Filling fails with `InvalidInput` if the buffer is shorter than N bytes.
+#[derive(Debug, Clone, Copy)]
+pub struct ExactNFill {
+    n: usize,
+    filled: usize,
+}
+
+impl ExactNFill {
+    /// Creates a strategy producing chunks of `n` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is 0.
+    pub fn new(n: usize) -> ExactNFill {
+        assert!(n > 0, "chunks must not be empty");
+        ExactNFill { n, filled: 0 }
+    }
+}
+
+impl<R: AsyncRead> FillStrategy<R> for ExactNFill {
+    fn poll_fill(&mut self, reader: &mut R, buf: &mut [u8]) -> Poll<usize, io::Error> {
+        if buf.len() < self.n {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "buffer is shorter than a chunk"));
+        }
+        while self.filled < self.n {
+            let n = try_nb!(reader.read(&mut buf[self.filled..self.n]));
+            if n == 0 {
+                break;
+            }
+            self.filled += n;
+        }
+        let filled = self.filled;
+        self.filled = 0;
+        Ok(Async::Ready(filled))
+    }
+}
+
+/// A future which copies data like [`Copy`], filling the buffer according to
+/// a [`FillStrategy`].
+///
+/// Created by the [`copy_with_fill_strategy`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`FillStrategy`]: trait.FillStrategy.html
+/// [`copy_with_fill_strategy`]: fn.copy_with_fill_strategy.html
+#[derive(Debug)]
+pub struct CopyFillStrategy<R, W, FS> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    strategy: Option<FS>,
+}
+
+/// Variant of [`copy_with_buffer`] where `strategy` decides how each chunk is
+/// read, e.g. [`ExactNFill`] for fixed-size records. The strategy is returned
+/// back on success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`ExactNFill`]: struct.ExactNFill.html
+pub fn copy_with_fill_strategy<R, W, FS>(reader: R, writer: W, buffer: Box<[u8]>, strategy: FS)
+    -> CopyFillStrategy<R, W, FS>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          FS: FillStrategy<R>,
+{
+    CopyFillStrategy {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        strategy: Some(strategy),
+    }
+}
+
+impl<R, W, FS> Future for CopyFillStrategy<R, W, FS>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          FS: FillStrategy<R>,
+{
+    type Item = (u64, R, W, Box<[u8]>, FS);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, FS), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to fill it to continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let strategy = self.strategy.as_mut().unwrap();
+                let n = try_ready!(strategy.poll_fill(reader, buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let strategy = self.strategy.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, strategy).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+    use std::io::{self, Cursor, Read};
+
+    use futures::Future;
+
+    use super::*;
+
+    /// Hands out at most 3 bytes per read.
+    #[derive(Debug)]
+    struct Trickle(Cursor<Vec<u8>>);
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = cmp::min(buf.len(), 3);
+            self.0.read(&mut buf[..len])
+        }
+    }
+
+    impl AsyncRead for Trickle {}
+
+    /// Records the length of every write.
+    #[derive(Debug)]
+    struct Chunks(Vec<usize>);
+
+    impl io::Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Chunks {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    #[test]
+    fn exact_chunks_from_short_reads() {
+        let reader = Trickle(Cursor::new(vec![1; 25]));
+        let (amt, _, writer, _, _) = copy_with_fill_strategy(reader, Chunks(Vec::new()),
+                                                             vec![0; 16].into_boxed_slice(),
+                                                             ExactNFill::new(10))
+            .wait()
+            .unwrap();
+        assert_eq!(amt, 25);
+        assert_eq!(writer.0, [10, 10, 5]);
+    }
+
+    #[test]
+    fn buffer_shorter_than_chunk() {
+        let e = copy_with_fill_strategy(Cursor::new(vec![1; 25]), Chunks(Vec::new()),
+                                        vec![0; 8].into_boxed_slice(), ExactNFill::new(10))
+            .wait()
+            .unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    #[should_panic(expected = "chunks must not be empty")]
+    fn empty_chunks_rejected() {
+        ExactNFill::new(0);
+    }
+}
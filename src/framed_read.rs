@@ -0,0 +1,103 @@
+use std::error::Error;
+use std::io;
+
+use futures::{Future, Poll, Stream};
+
+use tokio_codec::{Decoder, FramedRead};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which writes all frames of a `FramedRead` stream into a writer.
+///
+/// Created by the [`copy_from_framed`] function.
+///
+/// [`copy_from_framed`]: fn.copy_from_framed.html
+#[derive(Debug)]
+pub struct CopyFromFramed<R, W, C: Decoder> {
+    framed: Option<FramedRead<R, C>>,
+    read_done: bool,
+    writer: Option<W>,
+    frame: Option<C::Item>,
+    pos: usize,
+    amt: u64,
+}
+
+/// Creates a future which polls frames from `framed` and writes the bytes of
+/// each frame to `writer`. No separate buffer is needed as frames own their data.
+///
+/// Decoding errors are converted to `io::Error` of kind `InvalidData`.
+/// On success the number of bytes written, the `framed` stream and the
+/// `writer` are returned.
+///
+/// Available with the `tokio-codec` feature.
+pub fn copy_from_framed<R, W, C>(framed: FramedRead<R, C>, writer: W) -> CopyFromFramed<R, W, C>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          C: Decoder,
+          C::Item: AsRef<[u8]>,
+          C::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    CopyFromFramed {
+        framed: Some(framed),
+        read_done: false,
+        writer: Some(writer),
+        frame: None,
+        pos: 0,
+        amt: 0,
+    }
+}
+
+impl<R, W, C> Future for CopyFromFramed<R, W, C>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          C: Decoder,
+          C::Item: AsRef<[u8]>,
+          C::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Item = (u64, FramedRead<R, C>, W);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, FramedRead<R, C>, W), io::Error> {
+        loop {
+            // If we have no frame to write, get the next one.
+            if self.frame.is_none() && !self.read_done {
+                let framed = self.framed.as_mut().unwrap();
+                let frame = framed.poll().map_err(|e| {
+                    io::Error::new(io::ErrorKind::InvalidData, e)
+                });
+                match try_ready!(frame) {
+                    Some(frame) => {
+                        self.frame = Some(frame);
+                        self.pos = 0;
+                    }
+                    None => self.read_done = true,
+                }
+            }
+
+            // If we have a frame, let's write it out!
+            if let Some(ref frame) = self.frame {
+                let data = frame.as_ref();
+                let writer = self.writer.as_mut().unwrap();
+                while self.pos < data.len() {
+                    let i = try_nb!(writer.write(&data[self.pos..]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    } else {
+                        self.pos += i;
+                        self.amt += i as u64;
+                    }
+                }
+            }
+            self.frame = None;
+
+            // If we've written all the frames and the stream has ended, flush
+            // out the data and finish the transfer.
+            if self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let framed = self.framed.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                return Ok((self.amt, framed, writer).into())
+            }
+        }
+    }
+}
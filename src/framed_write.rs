@@ -0,0 +1,104 @@
+use std::io;
+
+use bytes::Bytes;
+
+use futures::{Async, AsyncSink, Future, Poll, Sink};
+
+use tokio_codec::{Encoder, FramedWrite};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data from a reader into a `FramedWrite` sink.
+///
+/// Created by the [`copy_framed`] function.
+///
+/// [`copy_framed`]: fn.copy_framed.html
+#[derive(Debug)]
+pub struct CopyFramed<R, W, C> {
+    reader: Option<R>,
+    read_done: bool,
+    framed: Option<FramedWrite<W, C>>,
+    pending: Option<Bytes>,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Creates a future which reads raw bytes from `reader` and sends each chunk
+/// to `framed` as a `Bytes` item, so that the codec encodes it before writing.
+///
+/// Backpressure of the sink is respected. Once `reader` hits EOF the sink is
+/// closed, i.e. flushed and shut down. On success the number of bytes sent,
+/// the `reader`, the `framed` sink and the buffer are returned.
+///
+/// Available with the `tokio-codec` feature.
+pub fn copy_framed<R, W, C>(reader: R, framed: FramedWrite<W, C>, buffer: Box<[u8]>)
+    -> CopyFramed<R, W, C>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          C: Encoder<Item = Bytes>,
+{
+    CopyFramed {
+        reader: Some(reader),
+        read_done: false,
+        framed: Some(framed),
+        pending: None,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W, C> Future for CopyFramed<R, W, C>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          C: Encoder<Item = Bytes>,
+{
+    type Item = (u64, R, FramedWrite<W, C>, Box<[u8]>);
+    type Error = C::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, FramedWrite<W, C>, Box<[u8]>), C::Error> {
+        loop {
+            // If there is a chunk which the sink has not accepted yet, try
+            // to hand it over again.
+            if let Some(item) = self.pending.take() {
+                let len = item.len();
+                let framed = self.framed.as_mut().unwrap();
+                if let AsyncSink::NotReady(item) = framed.start_send(item)? {
+                    self.pending = Some(item);
+                    return Ok(Async::NotReady);
+                }
+                self.amt += len as u64;
+            }
+
+            // Once all chunks are in the sink and we've seen EOF, close the
+            // sink to finish the transfer.
+            if self.read_done {
+                try_ready!(self.framed.as_mut().unwrap().close());
+                let reader = self.reader.take().unwrap();
+                let framed = self.framed.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, framed, buffer).into())
+            }
+
+            let r = {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                reader.read(buf)
+            };
+            let n = match r {
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // Nothing to read at the moment; let the sink write out
+                    // what it has buffered meanwhile.
+                    try_ready!(self.framed.as_mut().unwrap().poll_complete());
+                    return Ok(Async::NotReady);
+                }
+                Err(e) => return Err(e.into()),
+            };
+            if n == 0 {
+                self.read_done = true;
+            } else {
+                let buf = self.buffer.as_ref().unwrap();
+                self.pending = Some(Bytes::from(&buf[..n]));
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+use std::cmp;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Turns the chunks read by [`copy_framing`] into frames.
+///
+/// [`copy_framing`]: fn.copy_framing.html
+pub trait FramingProtocol {
+    /// Appends the frame for one chunk of `payload` to `out`.
+    fn frame(&mut self, payload: &[u8], out: &mut Vec<u8>);
+
+    /// Appends any trailer to `out` once the reader hits EOF.
+    fn finish(&mut self, out: &mut Vec<u8>);
+}
+
+/// Prefixes each chunk with its length as a big-endian number of 1, 2 or 4
+/// bytes.
+///
+/// Chunks too long for the prefix are split into several frames of the
+/// maximum length.
+#[derive(Debug, Clone, Copy)]
+pub struct LengthPrefixFramer {
+    width: usize,
+}
+
+impl LengthPrefixFramer {
+    /// Creates a framer with length prefixes `width` bytes wide.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `width` is not 1, 2 or 4.
+    pub fn new(width: u8) -> LengthPrefixFramer {
+        assert!(width == 1 || width == 2 || width == 4,
+                "unsupported length prefix width {}", width);
+        LengthPrefixFramer { width: width as usize }
+    }
+
+    /// Returns the width of the length prefixes in bytes.
+    pub fn width(&self) -> u8 {
+        self.width as u8
+    }
+}
+
+impl FramingProtocol for LengthPrefixFramer {
+    fn frame(&mut self, payload: &[u8], out: &mut Vec<u8>) {
+        let max = (1u64 << (8 * self.width)) - 1;
+        for chunk in payload.chunks(cmp::min(max, usize::MAX as u64) as usize) {
+            let len = (chunk.len() as u32).to_be_bytes();
+            out.extend_from_slice(&len[4 - self.width..]);
+            out.extend_from_slice(chunk);
+        }
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) {}
+}
+
+/// Appends a zero byte after each chunk.
+///
+/// The chunks themselves are not escaped, so this only suits data without
+/// zero bytes, like text.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NullTerminatedFramer;
+
+impl FramingProtocol for NullTerminatedFramer {
+    fn frame(&mut self, payload: &[u8], out: &mut Vec<u8>) {
+        out.extend_from_slice(payload);
+        out.push(0);
+    }
+
+    fn finish(&mut self, _out: &mut Vec<u8>) {}
+}
+
+/// A future which copies data like [`Copy`], writing each chunk as a frame.
+///
+/// Created by the [`copy_framing`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_framing`]: fn.copy_framing.html
+#[derive(Debug)]
+pub struct CopyFraming<R, W, Fr> {
+    reader: Option<R>,
+    read_done: bool,
+    finished: bool,
+    writer: Option<W>,
+    frame_buf: Vec<u8>,
+    frame_pos: usize,
+    pending: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    framer: Option<Fr>,
+}
+
+/// Variant of [`copy_with_buffer`] passing each chunk read through `framer`
+/// and writing the resulting frame instead. On EOF, the trailer from
+/// [`FramingProtocol::finish`] is written before the final flush.
+///
+/// The number of data bytes, framing excluded, and `framer` are returned on
+/// success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`FramingProtocol::finish`]: trait.FramingProtocol.html#tymethod.finish
+pub fn copy_framing<R, W, Fr>(reader: R, writer: W, buffer: Box<[u8]>, framer: Fr)
+    -> CopyFraming<R, W, Fr>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          Fr: FramingProtocol,
+{
+    CopyFraming {
+        reader: Some(reader),
+        read_done: false,
+        finished: false,
+        writer: Some(writer),
+        frame_buf: Vec::new(),
+        frame_pos: 0,
+        pending: 0,
+        amt: 0,
+        buffer: Some(buffer),
+        framer: Some(framer),
+    }
+}
+
+impl<R, W, Fr> Future for CopyFraming<R, W, Fr>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          Fr: FramingProtocol,
+{
+    type Item = (u64, R, W, Box<[u8]>, Fr);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, Fr), io::Error> {
+        loop {
+            // If the last frame is out, read some data and frame it, or
+            // frame the trailer on EOF.
+            if self.frame_pos == self.frame_buf.len() && !self.finished {
+                if self.read_done {
+                    self.frame_buf.clear();
+                    self.framer.as_mut().unwrap().finish(&mut self.frame_buf);
+                    self.frame_pos = 0;
+                    self.finished = true;
+                } else {
+                    let buf = self.buffer.as_mut().unwrap();
+                    let reader = self.reader.as_mut().unwrap();
+                    let n = try_nb!(reader.read(buf));
+                    if n == 0 {
+                        self.read_done = true;
+                        continue;
+                    }
+                    self.frame_buf.clear();
+                    self.framer.as_mut().unwrap().frame(&buf[..n], &mut self.frame_buf);
+                    self.frame_pos = 0;
+                    self.pending = n;
+                }
+            }
+
+            // If we have a frame, let's write it out!
+            while self.frame_pos < self.frame_buf.len() {
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&self.frame_buf[self.frame_pos..]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.frame_pos += i;
+                }
+            }
+            self.amt += self.pending as u64;
+            self.pending = 0;
+
+            // If we've written the trailer, flush out the data and finish the
+            // transfer.
+            if self.finished {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let framer = self.framer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, framer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use futures::Future;
+
+    use super::*;
+
+    fn framed<Fr: FramingProtocol>(data: &[u8], buffer_len: usize, framer: Fr) -> Vec<u8> {
+        let (amt, _, writer, _, _) = copy_framing(Cursor::new(data.to_vec()),
+                                                  Cursor::new(Vec::new()),
+                                                  vec![0; buffer_len].into_boxed_slice(),
+                                                  framer)
+            .wait()
+            .unwrap();
+        assert_eq!(amt, data.len() as u64);
+        writer.into_inner()
+    }
+
+    fn unframe_length_prefixed(mut framed: &[u8], width: usize) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while !framed.is_empty() {
+            let len = framed[..width].iter().fold(0, |len, &b| len << 8 | b as usize);
+            frames.push(framed[width..width + len].to_vec());
+            framed = &framed[width + len..];
+        }
+        frames
+    }
+
+    #[test]
+    fn length_prefix_round_trip() {
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        for &width in &[1, 2, 4] {
+            let out = framed(&data, 100, LengthPrefixFramer::new(width));
+            let frames = unframe_length_prefixed(&out, width as usize);
+            assert_eq!(frames.len(), 10);
+            assert_eq!(frames.concat(), data);
+        }
+    }
+
+    #[test]
+    fn length_prefix_splits_long_chunks() {
+        let data = vec![7; 600];
+        let out = framed(&data, 600, LengthPrefixFramer::new(1));
+        let frames = unframe_length_prefixed(&out, 1);
+        let lens: Vec<usize> = frames.iter().map(Vec::len).collect();
+        assert_eq!(lens, [255, 255, 90]);
+        assert_eq!(frames.concat(), data);
+    }
+
+    #[test]
+    #[should_panic(expected = "unsupported length prefix width 3")]
+    fn length_prefix_width_checked() {
+        LengthPrefixFramer::new(3);
+    }
+
+    #[test]
+    fn null_terminated_round_trip() {
+        let out = framed(b"hello world", 5, NullTerminatedFramer);
+        assert_eq!(out, b"hello\0 worl\0d\0");
+    }
+
+    /// Frames like `NullTerminatedFramer` and counts the frames in a trailer.
+    struct Counted(u8);
+
+    impl FramingProtocol for Counted {
+        fn frame(&mut self, payload: &[u8], out: &mut Vec<u8>) {
+            NullTerminatedFramer.frame(payload, out);
+            self.0 += 1;
+        }
+
+        fn finish(&mut self, out: &mut Vec<u8>) {
+            out.extend_from_slice(b"END");
+            out.push(self.0);
+        }
+    }
+
+    #[test]
+    fn trailer_written_last() {
+        assert_eq!(framed(b"abcdefg", 4, Counted(0)), b"abcd\0efg\0END\x02");
+        assert_eq!(framed(b"", 4, Counted(0)), b"END\x00");
+    }
+}
@@ -0,0 +1,301 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Handle for stopping a [`copy_with_graceful_abort`] future.
+///
+/// [`copy_with_graceful_abort`]: fn.copy_with_graceful_abort.html
+#[derive(Debug, Clone)]
+pub struct GracefulAbortHandle {
+    inner: Arc<AtomicBool>,
+}
+
+impl GracefulAbortHandle {
+    /// Asks the copy to stop once the current chunk is written.
+    ///
+    /// Takes effect the next time the copy is polled, so a copy waiting for
+    /// its reader keeps waiting until the reader wakes it up.
+    pub fn request_abort(&self) {
+        self.inner.store(true, Ordering::SeqCst);
+    }
+
+    /// Whether an abort has been requested.
+    pub fn is_abort_requested(&self) -> bool {
+        self.inner.load(Ordering::SeqCst)
+    }
+}
+
+/// State of an aborted [`copy_with_graceful_abort`] future.
+///
+/// [`copy_with_graceful_abort`]: fn.copy_with_graceful_abort.html
+pub struct CopyAbortedError<R, W> {
+    /// Number of bytes written before stopping.
+    pub bytes_transferred: u64,
+    /// The reader.
+    pub reader: R,
+    /// The writer, flushed.
+    pub writer: W,
+    /// The copy buffer.
+    pub buffer: Box<[u8]>,
+}
+
+impl<R, W> fmt::Debug for CopyAbortedError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyAbortedError")
+            .field("bytes_transferred", &self.bytes_transferred)
+            .finish()
+    }
+}
+
+impl<R, W> fmt::Display for CopyAbortedError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "copy aborted after {} bytes", self.bytes_transferred)
+    }
+}
+
+impl<R, W> Error for CopyAbortedError<R, W> {}
+
+/// Error of [`copy_with_graceful_abort`].
+///
+/// [`copy_with_graceful_abort`]: fn.copy_with_graceful_abort.html
+pub enum GracefulAbortError<R, W> {
+    /// The copy was stopped by [`GracefulAbortHandle::request_abort`].
+    ///
+    /// [`GracefulAbortHandle::request_abort`]: struct.GracefulAbortHandle.html#method.request_abort
+    Aborted(CopyAbortedError<R, W>),
+    /// Any I/O error.
+    Io(io::Error),
+}
+
+impl<R, W> fmt::Debug for GracefulAbortError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GracefulAbortError::Aborted(ref e) => f.debug_tuple("Aborted").field(e).finish(),
+            GracefulAbortError::Io(ref e) => f.debug_tuple("Io").field(e).finish(),
+        }
+    }
+}
+
+impl<R, W> fmt::Display for GracefulAbortError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GracefulAbortError::Aborted(ref e) => e.fmt(f),
+            GracefulAbortError::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl<R, W> Error for GracefulAbortError<R, W> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            GracefulAbortError::Aborted(_) => None,
+            GracefulAbortError::Io(ref e) => Some(e),
+        }
+    }
+}
+
+impl<R, W> From<io::Error> for GracefulAbortError<R, W> {
+    fn from(e: io::Error) -> GracefulAbortError<R, W> {
+        GracefulAbortError::Io(e)
+    }
+}
+
+impl<R, W> From<GracefulAbortError<R, W>> for io::Error {
+    fn from(e: GracefulAbortError<R, W>) -> io::Error {
+        match e {
+            GracefulAbortError::Aborted(e) => io::Error::other(e.to_string()),
+            GracefulAbortError::Io(e) => e,
+        }
+    }
+}
+
+/// A future which copies data like [`Copy`] until asked to stop.
+///
+/// Created by the [`copy_with_graceful_abort`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_graceful_abort`]: fn.copy_with_graceful_abort.html
+#[derive(Debug)]
+pub struct CopyGracefulAbort<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    aborted: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    abort: Arc<AtomicBool>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which can be stopped without dropping it
+/// in the middle of a chunk.
+///
+/// When the returned handle requests an abort, the copy finishes writing the
+/// chunk it has read, flushes the writer and then fails with
+/// [`GracefulAbortError::Aborted`], giving back the reader, writer and
+/// buffer. No more data is read after the abort is noticed.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`GracefulAbortError::Aborted`]: enum.GracefulAbortError.html#variant.Aborted
+pub fn copy_with_graceful_abort<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> (CopyGracefulAbort<R, W>, GracefulAbortHandle)
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    let abort = Arc::new(AtomicBool::new(false));
+    let handle = GracefulAbortHandle {
+        inner: abort.clone(),
+    };
+    let copy = CopyGracefulAbort {
+        reader: Some(reader),
+        read_done: false,
+        aborted: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        abort,
+        buffer: Some(buffer),
+    };
+    (copy, handle)
+}
+
+impl<R, W> Future for CopyGracefulAbort<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = GracefulAbortError<R, W>;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), GracefulAbortError<R, W>> {
+        loop {
+            // Stop reading once an abort is requested between chunks.
+            if self.pos == self.cap && !self.read_done && self.abort.load(Ordering::SeqCst) {
+                self.aborted = true;
+                self.read_done = true;
+            }
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer").into());
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF or an abort,
+            // flush out the data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                if self.aborted {
+                    return Err(GracefulAbortError::Aborted(CopyAbortedError {
+                        bytes_transferred: self.amt,
+                        reader,
+                        writer,
+                        buffer,
+                    }));
+                }
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+    use std::io::{self, Cursor};
+
+    use super::*;
+
+    /// Accepts 3 bytes per write and requests an abort on the first one.
+    #[derive(Debug)]
+    struct Aborting {
+        out: Vec<u8>,
+        flushed: bool,
+        handle: Option<GracefulAbortHandle>,
+    }
+
+    impl io::Write for Aborting {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if let Some(handle) = self.handle.take() {
+                handle.request_abort();
+            }
+            let n = cmp::min(buf.len(), 3);
+            self.out.extend_from_slice(&buf[..n]);
+            self.flushed = false;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushed = true;
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Aborting {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    #[test]
+    fn abort_finishes_the_chunk() {
+        let writer = Aborting { out: Vec::new(), flushed: false, handle: None };
+        let data: Vec<u8> = (0..100).collect();
+        let (mut copy, handle) = copy_with_graceful_abort(Cursor::new(data.clone()), writer,
+                                                          vec![0; 10].into_boxed_slice());
+        copy.writer.as_mut().unwrap().handle = Some(handle.clone());
+        match copy.wait() {
+            Err(GracefulAbortError::Aborted(e)) => {
+                assert_eq!(e.bytes_transferred, 10);
+                assert_eq!(e.reader.position(), 10);
+                assert_eq!(e.writer.out, &data[..10]);
+                assert!(e.writer.flushed);
+            }
+            r => panic!("not aborted: {:?}", r.map(|(amt, ..)| amt)),
+        }
+        assert!(handle.is_abort_requested());
+    }
+
+    #[test]
+    fn copies_everything_without_abort() {
+        let writer = Aborting { out: Vec::new(), flushed: false, handle: None };
+        let (copy, handle) = copy_with_graceful_abort(Cursor::new(vec![1; 25]), writer,
+                                                      vec![0; 10].into_boxed_slice());
+        let (amt, _, writer, _) = copy.wait().unwrap();
+        assert_eq!(amt, 25);
+        assert_eq!(writer.out, [1; 25]);
+        assert!(!handle.is_abort_requested());
+    }
+}
@@ -0,0 +1,103 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], but fails if the reader delivers
+/// more than a certain number of bytes.
+///
+/// Created by the [`copy_with_hard_limit`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_hard_limit`]: fn.copy_with_hard_limit.html
+#[derive(Debug)]
+pub struct CopyHardLimited<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    hard_limit: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which treats more than `hard_limit` bytes
+/// from the reader as a protocol violation, e.g. a peer sending more than its
+/// `Content-Length`.
+///
+/// The copy fails with `InvalidData` as soon as the excess is read; bytes over
+/// the limit are never written. Reaching EOF at or below the limit completes
+/// normally.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_hard_limit<R, W>(reader: R, writer: W, buffer: Box<[u8]>, hard_limit: u64)
+    -> CopyHardLimited<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyHardLimited {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        hard_limit,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyHardLimited<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else if self.amt + n as u64 > self.hard_limit {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                              "transfer exceeded hard limit"));
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
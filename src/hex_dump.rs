@@ -0,0 +1,125 @@
+use std::io::{self, Write};
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and writes a hex dump of every
+/// chunk for debugging.
+///
+/// Created by the [`copy_with_hex_dump`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_hex_dump`]: fn.copy_with_hex_dump.html
+#[derive(Debug)]
+pub struct CopyHexDump<R, W, Out> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    out: Option<Out>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for protocol debugging, which writes each
+/// chunk read to `out` as a hex dump in the style of Wireshark: 16 bytes per
+/// line with the offset in the chunk, the bytes in hex and printable ASCII.
+/// Chunks are separated by an empty line.
+///
+/// `out` is a blocking writer like `stderr()` or a `File`. Failing to write
+/// the dump fails the copy. `out` is returned on success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_hex_dump<R, W, Out>(reader: R, writer: W, buffer: Box<[u8]>, out: Out)
+    -> CopyHexDump<R, W, Out>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          Out: Write,
+{
+    CopyHexDump {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        out: Some(out),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+fn write_hex_dump<Out: Write>(out: &mut Out, data: &[u8]) -> io::Result<()> {
+    for (i, line) in data.chunks(16).enumerate() {
+        let mut text = format!("{:04x}  ", i * 16);
+        for k in 0..16 {
+            if k == 8 {
+                text.push(' ');
+            }
+            match line.get(k) {
+                Some(b) => text.push_str(&format!("{:02x} ", b)),
+                None => text.push_str("   "),
+            }
+        }
+        text.push(' ');
+        text.extend(line.iter().map(|&b| {
+            if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }
+        }));
+        writeln!(out, "{}", text)?;
+    }
+    writeln!(out)
+}
+
+impl<R, W, Out> Future for CopyHexDump<R, W, Out>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          Out: Write,
+{
+    type Item = (u64, R, W, Box<[u8]>, Out);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, Out), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, and dump it.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    write_hex_dump(self.out.as_mut().unwrap(), &buf[..n])?;
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let out = self.out.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, out).into())
+            }
+        }
+    }
+}
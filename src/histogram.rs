@@ -0,0 +1,153 @@
+use std::io;
+use std::time::Instant;
+
+use futures::{Future, Poll};
+
+use hdrhistogram::Histogram;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Latencies of successful `read` calls, in microseconds.
+#[derive(Debug, Clone)]
+pub struct ReadHistogram(pub Histogram<u64>);
+
+impl ReadHistogram {
+    /// Median read latency.
+    pub fn p50_read_us(&self) -> u64 {
+        self.0.value_at_quantile(0.5)
+    }
+
+    /// 99th percentile read latency.
+    pub fn p99_read_us(&self) -> u64 {
+        self.0.value_at_quantile(0.99)
+    }
+}
+
+/// Latencies of successful `write` calls, in microseconds.
+#[derive(Debug, Clone)]
+pub struct WriteHistogram(pub Histogram<u64>);
+
+impl WriteHistogram {
+    /// Median write latency.
+    pub fn p50_write_us(&self) -> u64 {
+        self.0.value_at_quantile(0.5)
+    }
+
+    /// 99th percentile write latency.
+    pub fn p99_write_us(&self) -> u64 {
+        self.0.value_at_quantile(0.99)
+    }
+}
+
+/// A future which copies data like [`Copy`] and records how long each read
+/// and write call takes.
+///
+/// Created by the [`copy_with_histogram`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_histogram`]: fn.copy_with_histogram.html
+#[derive(Debug)]
+pub struct CopyHistogram<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    reads: Option<Histogram<u64>>,
+    writes: Option<Histogram<u64>>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which records the latency of every
+/// successful `read` and `write` call into a histogram.
+///
+/// Calls returning `WouldBlock` are not recorded. On success the histograms
+/// are returned after the amount copied; values beyond the histogram range
+/// are clamped.
+///
+/// Available with the `hdrhistogram` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_histogram<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyHistogram<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyHistogram {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        reads: Some(new_histogram()),
+        writes: Some(new_histogram()),
+        buffer: Some(buffer),
+    }
+}
+
+fn new_histogram() -> Histogram<u64> {
+    Histogram::new(3).expect("3 significant figures are supported")
+}
+
+fn record_since(hist: &mut Option<Histogram<u64>>, start: Instant) {
+    let us = start.elapsed().as_micros();
+    let us = if us > u64::MAX as u128 { u64::MAX } else { us as u64 };
+    hist.as_mut().unwrap().saturating_record(us);
+}
+
+impl<R, W> Future for CopyHistogram<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, ReadHistogram, WriteHistogram, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, ReadHistogram, WriteHistogram, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let start = Instant::now();
+                let n = try_nb!(reader.read(buf));
+                record_since(&mut self.reads, start);
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let start = Instant::now();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                record_since(&mut self.writes, start);
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reads = ReadHistogram(self.reads.take().unwrap());
+                let writes = WriteHistogram(self.writes.take().unwrap());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reads, writes, reader, writer, buffer).into())
+            }
+        }
+    }
+}
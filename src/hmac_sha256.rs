@@ -0,0 +1,118 @@
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and computes HMAC-SHA256 of
+/// everything it transferred.
+///
+/// Created by the [`copy_with_hmac`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_hmac`]: fn.copy_with_hmac.html
+pub struct CopyHmac<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    mac: Option<Hmac<Sha256>>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which authenticates the copied data with
+/// HMAC-SHA256 keyed by `key`.
+///
+/// The MAC is updated with each chunk as soon as it is read, before it is
+/// written. On success the 32-byte tag is returned after the number of bytes.
+///
+/// Available with the `hmac` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_hmac<R, W>(reader: R, writer: W, buffer: Box<[u8]>, key: &[u8]) -> CopyHmac<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyHmac {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        mac: Some(Hmac::new_from_slice(key).expect("HMAC accepts keys of any length")),
+        buffer: Some(buffer),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug> fmt::Debug for CopyHmac<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyHmac")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W> Future for CopyHmac<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, [u8; 32], R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, [u8; 32], R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.mac.as_mut().unwrap().update(&buf[..n]);
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let tag = self.mac.take().unwrap().finalize().into_bytes().into();
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, tag, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use indicatif::ProgressBar;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and shows the progress on an
+/// `indicatif` progress bar.
+///
+/// Created by the [`copy_with_indicatif`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_indicatif`]: fn.copy_with_indicatif.html
+#[derive(Debug)]
+pub struct CopyIndicatif<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    bar: ProgressBar,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which advances `bar` by the number of bytes
+/// of each write and finishes it once the copy is done.
+///
+/// If the bar has a length set, its ETA and rate display work as usual.
+/// `ProgressBar` is cheap to clone, so keep a clone if you need it afterwards.
+///
+/// Available with the `indicatif` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_indicatif<R, W>(reader: R, writer: W, buffer: Box<[u8]>, bar: ProgressBar)
+    -> CopyIndicatif<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyIndicatif {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        bar,
+        buffer: Some(buffer),
+    }
+}
+
+/// Like [`copy_with_indicatif`], but also sets the length of `bar` to
+/// `total_size` up front.
+///
+/// Available with the `indicatif` feature.
+///
+/// [`copy_with_indicatif`]: fn.copy_with_indicatif.html
+pub fn copy_with_progress_bar_and_size<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                             bar: ProgressBar, total_size: u64)
+    -> CopyIndicatif<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    bar.set_length(total_size);
+    copy_with_indicatif(reader, writer, buffer, bar)
+}
+
+impl<R, W> Future for CopyIndicatif<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                    self.bar.inc(i as u64);
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                self.bar.finish();
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,297 @@
+use std::cmp;
+use std::io;
+use std::mem;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use futures::{task, Async, Future, Poll};
+use futures::task::Task;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+#[derive(Debug)]
+struct Budget {
+    inflight: AtomicUsize,
+    cap: usize,
+    waiters: Mutex<Vec<Task>>,
+}
+
+/// Budget of bytes read but not yet written, shared by a group of
+/// [`copy_with_inflight_limit`] futures.
+///
+/// [`copy_with_inflight_limit`]: fn.copy_with_inflight_limit.html
+#[derive(Debug, Clone)]
+pub struct InflightBudget {
+    inner: Arc<Budget>,
+}
+
+impl InflightBudget {
+    /// Creates a budget allowing up to `cap` bytes in flight.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `cap` is zero.
+    pub fn new(cap: usize) -> InflightBudget {
+        assert!(cap > 0, "in-flight cap must not be zero");
+        InflightBudget {
+            inner: Arc::new(Budget {
+                inflight: AtomicUsize::new(0),
+                cap,
+                waiters: Mutex::new(Vec::new()),
+            }),
+        }
+    }
+
+    /// Number of bytes currently in flight.
+    pub fn in_flight(&self) -> usize {
+        self.inner.inflight.load(Ordering::Acquire)
+    }
+
+    /// Adds `n` bytes unless that goes over the cap.
+    fn try_add(&self, n: usize) -> bool {
+        let before = self.inner.inflight.fetch_add(n, Ordering::Acquire);
+        if before + n > self.inner.cap {
+            self.inner.inflight.fetch_sub(n, Ordering::Release);
+            false
+        } else {
+            true
+        }
+    }
+
+    /// Reserves `n` bytes, or registers the current task to be woken up when
+    /// some are released.
+    fn poll_reserve(&self, n: usize) -> Async<()> {
+        if self.try_add(n) {
+            return Async::Ready(());
+        }
+        // Register before trying again, so that a release between the two
+        // attempts is not missed.
+        self.inner.waiters.lock().unwrap().push(task::current());
+        if self.try_add(n) {
+            Async::Ready(())
+        } else {
+            Async::NotReady
+        }
+    }
+
+    /// Gives back `n` bytes, waking up the copies waiting for room.
+    fn release(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        self.inner.inflight.fetch_sub(n, Ordering::Release);
+        let waiters = mem::take(&mut *self.inner.waiters.lock().unwrap());
+        for waiter in waiters {
+            waiter.notify();
+        }
+    }
+}
+
+/// A future which copies data like [`Copy`], but limits how much data read
+/// and not yet written may exist across a group of copies.
+///
+/// Created by the [`copy_with_inflight_limit`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_inflight_limit`]: fn.copy_with_inflight_limit.html
+#[derive(Debug)]
+pub struct CopyInflight<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    budget: InflightBudget,
+    my_quota: usize,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for managing memory pressure across many
+/// concurrent copies.
+///
+/// `budget` counts the bytes read but not yet written by all copies sharing
+/// it. Before each read, the read size (the buffer length, but at most
+/// `my_quota` and the cap of `budget`) is reserved from it; if that would
+/// take the count over the cap, the copy waits until other copies write out
+/// some of their data. The unused part of a reservation is given back after
+/// the read, the rest as the data is written out, so a copy never has more
+/// than `my_quota` bytes in flight.
+///
+/// # Panics
+///
+/// Panics if `buffer` is empty or `my_quota` is zero.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_inflight_limit<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                      budget: InflightBudget, my_quota: usize)
+    -> CopyInflight<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(!buffer.is_empty(), "buffer must not be empty");
+    assert!(my_quota > 0, "quota must not be zero");
+    CopyInflight {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        budget,
+        my_quota,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyInflight<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to reserve room and read
+            // some data to continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let want = cmp::min(cmp::min(buf.len(), self.my_quota), self.budget.inner.cap);
+                if let Async::NotReady = self.budget.poll_reserve(want) {
+                    return Ok(Async::NotReady);
+                }
+
+                let reader = self.reader.as_mut().unwrap();
+                let res = reader.read(&mut buf[..want]);
+                let n = *res.as_ref().unwrap_or(&0);
+                self.budget.release(want - n);
+                let n = try_nb!(res);
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                    self.budget.release(i);
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+impl<R, W> Drop for CopyInflight<R, W> {
+    fn drop(&mut self) {
+        // Data which will never be written is not in flight anymore.
+        self.budget.release(self.cap - self.pos);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Write};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use futures::{Async, Poll};
+    use futures::executor::{self, Notify, NotifyHandle};
+
+    use tokio_io::AsyncWrite;
+
+    use super::*;
+
+    struct Count(AtomicUsize);
+
+    impl Notify for Count {
+        fn notify(&self, _: usize) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Refuses the first write without arranging a wakeup.
+    struct Hesitant {
+        refused: bool,
+        out: Vec<u8>,
+    }
+
+    impl Write for Hesitant {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !self.refused {
+                self.refused = true;
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            self.out.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Hesitant {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    #[test]
+    fn waits_for_released_budget() {
+        let budget = InflightBudget::new(4);
+        let a_count = Arc::new(Count(AtomicUsize::new(0)));
+        let b_count = Arc::new(Count(AtomicUsize::new(0)));
+        let a_notify = NotifyHandle::from(a_count.clone());
+        let b_notify = NotifyHandle::from(b_count.clone());
+
+        let writer = Hesitant { refused: false, out: Vec::new() };
+        let mut a = executor::spawn(copy_with_inflight_limit(
+            Cursor::new(vec![1; 8]), writer, vec![0; 16].into_boxed_slice(), budget.clone(), 16));
+        let mut b = executor::spawn(copy_with_inflight_limit(
+            Cursor::new(vec![2; 8]), Cursor::new(Vec::new()), vec![0; 16].into_boxed_slice(),
+            budget.clone(), 16));
+
+        // The first copy takes the whole budget and stalls on its writer, so
+        // the second one has to wait, without waking itself up.
+        assert!(a.poll_future_notify(&a_notify, 0).unwrap().is_not_ready());
+        assert_eq!(budget.in_flight(), 4);
+        assert!(b.poll_future_notify(&b_notify, 0).unwrap().is_not_ready());
+        assert_eq!(b_count.0.load(Ordering::SeqCst), 0);
+
+        // Writing out the first chunk wakes up the second copy.
+        match a.poll_future_notify(&a_notify, 0).unwrap() {
+            Async::Ready((n, _, writer, _)) => {
+                assert_eq!(n, 8);
+                assert_eq!(writer.out, [1; 8]);
+            }
+            Async::NotReady => panic!("first copy did not finish"),
+        }
+        assert!(b_count.0.load(Ordering::SeqCst) > 0);
+        match b.poll_future_notify(&b_notify, 0).unwrap() {
+            Async::Ready((n, _, _, _)) => assert_eq!(n, 8),
+            Async::NotReady => panic!("second copy did not finish"),
+        }
+        assert_eq!(budget.in_flight(), 0);
+    }
+}
@@ -0,0 +1,263 @@
+use std::cmp;
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+
+use rand;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+
+fn is_retryable(kind: io::ErrorKind) -> bool {
+    matches!(kind,
+             io::ErrorKind::Interrupted |
+             io::ErrorKind::TimedOut |
+             io::ErrorKind::ConnectionReset |
+             io::ErrorKind::ConnectionAborted |
+             io::ErrorKind::BrokenPipe)
+}
+
+/// A future which copies data like [`Copy`], retrying failed operations
+/// after a growing, randomized delay.
+///
+/// Created by the [`copy_with_jittered_retry`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_jittered_retry`]: fn.copy_with_jittered_retry.html
+#[derive(Debug)]
+pub struct CopyJitteredRetry<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    max_attempts: u32,
+    attempt: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    sleep: Option<Delay>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_read_retry`] which also retries writes and the
+/// final flush, and waits before each retry instead of retrying at once, so
+/// that many clients do not hit a recovering server at the same moment.
+///
+/// `Interrupted`, `TimedOut`, `ConnectionReset`, `ConnectionAborted` and
+/// `BrokenPipe` errors are retried up to `max_attempts` times in a row. The
+/// n-th retry in a row waits `base_delay * 2^(n - 1)` plus a random jitter of
+/// up to 25% of that, at most `max_delay`. The retried operation continues
+/// where the failed one left off, and the streak is reset after each
+/// successful operation.
+/// Needs to be run within a `tokio-timer` timer context.
+///
+/// Available with the `tokio-timer` and `rand` features.
+///
+/// [`copy_with_read_retry`]: fn.copy_with_read_retry.html
+pub fn copy_with_jittered_retry<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                      max_attempts: u32, base_delay: Duration,
+                                      max_delay: Duration) -> CopyJitteredRetry<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyJitteredRetry {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        max_attempts,
+        attempt: 0,
+        base_delay,
+        max_delay,
+        sleep: None,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> CopyJitteredRetry<R, W> {
+    /// Schedules a retry after `e`, or gives it back if it can't be retried.
+    fn retry(&mut self, e: io::Error) -> io::Result<()> {
+        if !is_retryable(e.kind()) || self.attempt >= self.max_attempts {
+            return Err(e);
+        }
+        let factor = 1u32.checked_shl(self.attempt).unwrap_or(u32::MAX);
+        let backoff = self.base_delay.saturating_mul(factor);
+        let jitter = backoff.mul_f64(rand::random::<f64>() * 0.25);
+        let delay = cmp::min(backoff.saturating_add(jitter), self.max_delay);
+        self.sleep = Some(Delay::new(Instant::now() + delay));
+        self.attempt += 1;
+        Ok(())
+    }
+}
+
+impl<R, W> Future for CopyJitteredRetry<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // Wait out the delay before a retry.
+            if let Some(ref mut sleep) = self.sleep {
+                match sleep.poll() {
+                    Ok(Async::Ready(())) => {}
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(e) => return Err(io::Error::other(e)),
+                }
+            }
+            self.sleep = None;
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                match reader.read(buf) {
+                    Ok(0) => self.read_done = true,
+                    Ok(n) => {
+                        self.pos = 0;
+                        self.cap = n;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(e) => {
+                        self.retry(e)?;
+                        continue;
+                    }
+                }
+                self.attempt = 0;
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                match writer.write(&buf[self.pos..self.cap]) {
+                    Ok(0) => {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    }
+                    Ok(i) => {
+                        self.pos += i;
+                        self.amt += i as u64;
+                        self.attempt = 0;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(e) => {
+                        self.retry(e)?;
+                        break;
+                    }
+                }
+            }
+            if self.sleep.is_some() {
+                continue;
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                match self.writer.as_mut().unwrap().flush() {
+                    Ok(()) => {}
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(e) => {
+                        self.retry(e)?;
+                        continue;
+                    }
+                }
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    /// Replays the given reads, then hits EOF.
+    #[derive(Debug)]
+    struct Script(Vec<Result<&'static [u8], io::ErrorKind>>);
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Ok(0);
+            }
+            let data = self.0.remove(0)?;
+            buf[..data.len()].copy_from_slice(data);
+            Ok(data.len())
+        }
+    }
+
+    impl AsyncRead for Script {}
+
+    fn copy(reads: Vec<Result<&'static [u8], io::ErrorKind>>, max_attempts: u32,
+            base_delay: Duration, max_delay: Duration) -> io::Result<Vec<u8>> {
+        let copy = copy_with_jittered_retry(Script(reads), Cursor::new(Vec::new()),
+                                            vec![0; 8].into_boxed_slice(), max_attempts,
+                                            base_delay, max_delay);
+        Runtime::new().unwrap().block_on(copy).map(|(_, _, writer, _)| writer.into_inner())
+    }
+
+    const RESET: Result<&'static [u8], io::ErrorKind> = Err(io::ErrorKind::ConnectionReset);
+
+    fn data(data: &'static [u8]) -> Result<&'static [u8], io::ErrorKind> {
+        Ok(data)
+    }
+
+    #[test]
+    fn streak_reset_by_success() {
+        let reads = vec![RESET, RESET, data(b"ab"), RESET, RESET, data(b"cd"), RESET, RESET];
+        let out = copy(reads, 2, Duration::from_millis(1), Duration::from_secs(1)).unwrap();
+        assert_eq!(out, b"abcd");
+    }
+
+    #[test]
+    fn gives_up_after_max_attempts() {
+        let reads = vec![data(b"ab"), RESET, RESET, RESET, data(b"cd")];
+        let e = copy(reads, 2, Duration::from_millis(1), Duration::from_secs(1)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    #[test]
+    fn other_errors_not_retried() {
+        let reads = vec![Err(io::ErrorKind::PermissionDenied), data(b"ab")];
+        let e = copy(reads, 5, Duration::from_millis(1), Duration::from_secs(1)).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn delay_doubles_up_to_max() {
+        let reads = vec![RESET, RESET, RESET, data(b"ab")];
+        let start = Instant::now();
+        copy(reads.clone(), 3, Duration::from_millis(40), Duration::from_secs(1)).unwrap();
+        // 40 + 80 + 160 ms, plus up to 25% jitter each.
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(280), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(1000), "{:?}", elapsed);
+
+        let start = Instant::now();
+        copy(reads, 3, Duration::from_millis(40), Duration::from_millis(50)).unwrap();
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(140), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(280), "{:?}", elapsed);
+    }
+}
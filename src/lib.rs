@@ -2,10 +2,44 @@
 //! buffer and retrieve it after copying. This may increase performance in some cases.
 //! [`tokio_io::io::copy` function]: https://docs.rs/tokio-io/0.1/tokio_io/io/fn.copy.html
 #![deny(missing_docs)]
+#![cfg_attr(feature = "allocator_api", feature(allocator_api))]
+#![cfg_attr(feature = "async_iterator", feature(async_iterator))]
 
+#[macro_use]
 extern crate futures;
 #[macro_use]
 extern crate tokio_io;
+extern crate bytes;
+#[cfg(unix)]
+extern crate libc;
+#[cfg(feature = "hmac")]
+extern crate hmac;
+#[cfg(feature = "hmac")]
+extern crate sha2;
+#[cfg(feature = "tokio-codec")]
+extern crate tokio_codec;
+#[cfg(feature = "tokio-timer")]
+extern crate tokio_timer;
+#[cfg(feature = "lz4")]
+extern crate lz4_flex;
+#[cfg(feature = "indicatif")]
+extern crate indicatif;
+#[cfg(feature = "hdrhistogram")]
+extern crate hdrhistogram;
+#[cfg(feature = "ringbuf")]
+extern crate ringbuf;
+#[cfg(feature = "flate2")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "rand")]
+extern crate rand;
+#[cfg(target_os = "linux")]
+extern crate mio;
+#[cfg(target_os = "linux")]
+extern crate tokio_reactor;
+#[cfg(test)]
+extern crate tokio;
 
 use std::io;
 
@@ -13,6 +47,187 @@ use futures::{Future, Poll};
 
 use tokio_io::{AsyncRead, AsyncWrite};
 
+mod cooperative;
+mod read_shutdown;
+#[cfg(feature = "hmac")]
+mod hmac_sha256;
+mod rate_limiter;
+mod async_progress;
+mod unified;
+mod buffer_stats;
+#[cfg(feature = "tokio-codec")]
+mod framed_write;
+#[cfg(feature = "tokio-codec")]
+mod framed_read;
+mod padding;
+mod concurrent_flush;
+mod read_retry;
+#[cfg(feature = "tokio-timer")]
+mod chunk_deadline;
+mod write_observer;
+mod hard_limit;
+#[cfg(all(feature = "sparse", target_os = "linux"))]
+mod sparse;
+mod local;
+mod report;
+mod parallel_multicast;
+mod error_context;
+mod limited_excess;
+mod transform_in_place;
+mod async_transform;
+#[cfg(feature = "lz4")]
+mod lz4;
+mod fill_strategy;
+mod write_buffered;
+#[cfg(feature = "indicatif")]
+mod indicatif_bar;
+#[cfg(unix)]
+mod socket;
+#[cfg(feature = "hdrhistogram")]
+mod histogram;
+mod arc;
+mod backpressure_fn;
+#[cfg(feature = "tokio-timer")]
+mod eof_timeout;
+#[cfg(unix)]
+mod udp_batch;
+mod pool;
+mod partial;
+mod two_phase;
+mod crc32;
+mod crc32_framed;
+mod write_gather;
+#[cfg(feature = "allocator_api")]
+mod allocator;
+mod priority;
+mod write_failover;
+mod audit;
+mod inflight;
+#[cfg(feature = "async_iterator")]
+mod async_iter;
+mod hex_dump;
+mod sniff;
+mod slow_start;
+mod pooled;
+mod quic;
+#[cfg(target_os = "linux")]
+mod fd_ready;
+#[cfg(target_os = "linux")]
+mod tee_splice;
+mod chunk_ack;
+#[cfg(unix)]
+mod nagle_toggle;
+#[cfg(feature = "tokio-timer")]
+mod threshold_throttle;
+mod tls_aligned;
+mod shrink;
+#[cfg(feature = "ringbuf")]
+mod ring_buffer;
+mod graceful_abort;
+mod min_read;
+mod framing;
+mod byteswap;
+#[cfg(feature = "tokio-timer")]
+mod phase_timeout;
+mod atomic_chunk;
+#[cfg(all(feature = "vmsplice", target_os = "linux"))]
+mod vmsplice;
+mod autocompress;
+mod write_eof;
+mod channels;
+#[cfg(all(feature = "tokio-timer", feature = "rand"))]
+mod jittered_retry;
+mod multipart;
+
+pub use cooperative::{copy_cooperative, CopyCooperative};
+pub use read_shutdown::{copy_with_read_shutdown, CopyReadShutdown};
+#[cfg(feature = "hmac")]
+pub use hmac_sha256::{copy_with_hmac, CopyHmac};
+pub use rate_limiter::{copy_with_rate_limiter, CopyRateLimiter, RateLimiter};
+pub use async_progress::{copy_with_async_progress, CopyAsyncProgress};
+pub use unified::{copy_from_unified, CopyFromUnified};
+pub use buffer_stats::{copy_with_buffer_stats, BufferStats, CopyBufferStats};
+#[cfg(feature = "tokio-codec")]
+pub use framed_write::{copy_framed, CopyFramed};
+#[cfg(feature = "tokio-codec")]
+pub use framed_read::{copy_from_framed, CopyFromFramed};
+pub use padding::{copy_with_padding, CopyWithPadding};
+pub use concurrent_flush::{copy_with_concurrent_flush, CopyConcurrentFlush};
+pub use read_retry::{copy_with_read_retry, CopyReadRetry};
+#[cfg(feature = "tokio-timer")]
+pub use chunk_deadline::{copy_per_chunk_deadline, CopyChunkDeadline};
+pub use write_observer::{copy_with_write_observer, CopyWriteObserver};
+pub use hard_limit::{copy_with_hard_limit, CopyHardLimited};
+#[cfg(all(feature = "sparse", target_os = "linux"))]
+pub use sparse::{copy_sparse, CopySparse};
+pub use local::{copy_local, CopyLocal};
+pub use report::{copy_with_report, CopyReport};
+pub use parallel_multicast::{copy_parallel_multicast, CopyParallelMulticast};
+pub use error_context::{copy_with_error_context, CopyCtx, CopyErrorContext, CopyPhase};
+pub use limited_excess::{copy_with_limit_and_excess, CopyLimitedExcess};
+pub use transform_in_place::{copy_transform_in_place, CopyInPlace};
+pub use async_transform::{copy_async_transform, CopyAsyncTransform};
+#[cfg(feature = "lz4")]
+pub use lz4::{copy_lz4_framed, copy_lz4_unframed, CopyLz4Framed, CopyLz4Unframed};
+pub use fill_strategy::{copy_with_fill_strategy, CopyFillStrategy, ExactNFill, FillStrategy, GreedyFill};
+pub use write_buffered::{copy_write_buffered, CopyWriteBuffered};
+#[cfg(feature = "indicatif")]
+pub use indicatif_bar::{copy_with_indicatif, copy_with_progress_bar_and_size, CopyIndicatif};
+#[cfg(unix)]
+pub use socket::{copy_with_keepalive, copy_with_socket_tuning};
+#[cfg(feature = "hdrhistogram")]
+pub use histogram::{copy_with_histogram, CopyHistogram, ReadHistogram, WriteHistogram};
+pub use arc::{copy_arc, CopyArc};
+pub use backpressure_fn::{copy_with_backpressure_fn, CopyBpFn};
+#[cfg(feature = "tokio-timer")]
+pub use eof_timeout::{copy_with_eof_timeout, CopyEofTimeout};
+#[cfg(unix)]
+pub use udp_batch::{copy_udp_batch, CopyUdpBatch};
+pub use pool::{BufferPool, PooledCopy};
+pub use partial::{copy_partial, extract_buffer, CopyPartial, PartialCopyError};
+pub use two_phase::{copy_two_phase, CopyTwoPhase};
+pub use crc32_framed::{copy_framed_crc32, copy_unframe_crc32, CopyFramedCrc32, CopyUnframeCrc32};
+pub use write_gather::{copy_write_gather, CopyWriteGather};
+#[cfg(feature = "allocator_api")]
+pub use allocator::{copy_with_allocator, CopyWithAllocator};
+pub use priority::{copy_with_priority, CopyPrioritized, PriorityQueue};
+pub use write_failover::{copy_with_write_failover, CopyWriteFailover};
+pub use audit::{copy_with_audit, CopyAudit};
+pub use inflight::{copy_with_inflight_limit, CopyInflight, InflightBudget};
+#[cfg(feature = "async_iterator")]
+pub use async_iter::{copy_from_async_iter, CopyFromAsyncIter};
+pub use hex_dump::{copy_with_hex_dump, CopyHexDump};
+pub use sniff::{copy_with_sniff, CopySniff};
+pub use slow_start::{copy_slow_start, CopySlowStart};
+pub use pooled::{copy_pooled, CopyPooled};
+pub use quic::{copy_quic, CopyQuic, CopyQuicError, QuicResetCode};
+#[cfg(target_os = "linux")]
+pub use tee_splice::{copy_tee_splice, CopyTeeSplice};
+pub use chunk_ack::{copy_with_chunk_ack, CopyChunkAck};
+#[cfg(unix)]
+pub use nagle_toggle::{copy_with_nagle_toggle, CopyNagleToggle};
+#[cfg(feature = "tokio-timer")]
+pub use threshold_throttle::{copy_threshold_throttle, CopyThrottleThreshold, ThrottleMode};
+pub use tls_aligned::{copy_tls_aligned, CopyTlsAligned};
+pub use shrink::{copy_shrink, CopyShrink};
+#[cfg(feature = "ringbuf")]
+pub use ring_buffer::{ring_buf_pair, RingBufReader, RingBufWriter};
+pub use graceful_abort::{copy_with_graceful_abort, CopyAbortedError, CopyGracefulAbort, GracefulAbortError, GracefulAbortHandle};
+pub use min_read::{copy_with_min_read, CopyMinRead};
+pub use framing::{copy_framing, CopyFraming, FramingProtocol, LengthPrefixFramer, NullTerminatedFramer};
+pub use byteswap::{copy_byteswap, ByteSwapSize, CopyByteSwap};
+#[cfg(feature = "tokio-timer")]
+pub use phase_timeout::{copy_multi_phase_timeout, CopyMultiPhaseTimeout, CopyPhaseTimeout, MultiPhaseTimeout};
+pub use atomic_chunk::{copy_atomic_chunk_write, CopyAtomicChunk};
+#[cfg(all(feature = "vmsplice", target_os = "linux"))]
+pub use vmsplice::{copy_vmsplice, CopyVmsplice};
+pub use autocompress::{copy_autocompress, CopyAutoCompress, DetectedFormat};
+pub use write_eof::{copy_with_write_eof_ok, CopyResult, CopyWriteEofOk};
+pub use channels::{copy_between_channels, copy_mpsc_to_async_write, CopyChannels, CopyMpscToWrite};
+#[cfg(all(feature = "tokio-timer", feature = "rand"))]
+pub use jittered_retry::{copy_with_jittered_retry, CopyJitteredRetry};
+pub use multipart::{copy_multipart, CopyMultipart};
+
 /// A future which will copy all data from a reader into a writer.
 ///
 /// Created by the [`copy_with_buffer`] function, this future will resolve to the number of
@@ -54,8 +269,12 @@ pub fn copy<R, W>(reader: R, writer: W) -> Copy<R, W>
 /// Advanced version of [`copy`] where you can specify your own buffer.
 /// Buffer may be reused for multiple copy operations.
 ///
+/// The returned future is `Send` only if both `reader` and `writer` are.
+/// For `!Send` ones see also [`copy_local`].
+///
 /// For other description text see the [`copy` function documentation].
 /// [`copy` function documentation]: fn.copy.html
+/// [`copy_local`]: fn.copy_local.html
 pub fn copy_with_buffer<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> Copy<R, W>
     where R: AsyncRead,
           W: AsyncWrite,
@@ -98,7 +317,7 @@ impl<R, W> Future for Copy<R, W>
             while self.pos < self.cap {
                 let buf = self.buffer.as_mut().unwrap();
                 let writer = self.writer.as_mut().unwrap();
-                let i = try_nb!(writer.write(&mut buf[self.pos..self.cap]));
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
                 if i == 0 {
                     return Err(io::Error::new(io::ErrorKind::WriteZero,
                                               "write zero byte into writer"));
@@ -0,0 +1,182 @@
+use std::io;
+use std::mem;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies up to a certain number of bytes and keeps whatever
+/// was read beyond that.
+///
+/// Created by the [`copy_with_limit_and_excess`] function.
+///
+/// [`copy_with_limit_and_excess`]: fn.copy_with_limit_and_excess.html
+#[derive(Debug)]
+pub struct CopyLimitedExcess<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    read_amt: u64,
+    limit: u64,
+    excess: Vec<u8>,
+    drained: bool,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which writes at most `limit` bytes, for
+/// fixed-length messages followed by other data.
+///
+/// Reads use the whole buffer, so the read which crosses `limit` may return
+/// bytes belonging to whatever follows. Those bytes are not written but
+/// returned as a `Vec<u8>` after the number of bytes copied, so that e.g. a
+/// protocol parser can continue with them. Once `limit` bytes are written,
+/// the reader is also drained into the `Vec<u8>` until it would block or hits
+/// EOF, so that no data it has already buffered is left behind. A reader
+/// which never blocks is thus read to the end.
+///
+/// The copy completes once `limit` bytes are written and the reader is
+/// drained, or once the reader hits EOF before `limit`.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_limit_and_excess<R, W>(reader: R, writer: W, buffer: Box<[u8]>, limit: u64)
+    -> CopyLimitedExcess<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyLimitedExcess {
+        reader: Some(reader),
+        read_done: limit == 0,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        read_amt: 0,
+        limit,
+        excess: Vec::new(),
+        drained: false,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyLimitedExcess<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, Vec<u8>, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, Vec<u8>, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue. Whatever is over the limit is put aside.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    let remaining = self.limit - self.read_amt;
+                    let take = if (n as u64) < remaining { n } else { remaining as usize };
+                    self.excess.extend_from_slice(&buf[take..n]);
+                    self.read_amt += take as u64;
+                    self.read_done = self.read_amt == self.limit;
+                    self.pos = 0;
+                    self.cap = take;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF or the limit,
+            // flush out the data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                if self.read_amt == self.limit && !self.drained {
+                    let buf = self.buffer.as_mut().unwrap();
+                    let reader = self.reader.as_mut().unwrap();
+                    loop {
+                        match reader.read(buf) {
+                            Ok(0) => break,
+                            Ok(n) => self.excess.extend_from_slice(&buf[..n]),
+                            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                            Err(e) => return Err(e),
+                        }
+                    }
+                    self.drained = true;
+                }
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let excess = mem::take(&mut self.excess);
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, excess, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read};
+
+    use futures::Future;
+
+    use super::*;
+
+    /// Hands out the given chunks one read at a time, then would block.
+    #[derive(Debug)]
+    struct Chunks(Vec<&'static [u8]>);
+
+    impl Read for Chunks {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let chunk = self.0.remove(0);
+            buf[..chunk.len()].copy_from_slice(chunk);
+            Ok(chunk.len())
+        }
+    }
+
+    impl AsyncRead for Chunks {}
+
+    fn copy(chunks: Vec<&'static [u8]>, limit: u64) -> (u64, Vec<u8>, Vec<u8>) {
+        let (amt, excess, _, writer, _) =
+            copy_with_limit_and_excess(Chunks(chunks), io::Cursor::new(Vec::new()),
+                                       vec![0; 16].into_boxed_slice(), limit)
+                .wait()
+                .unwrap();
+        (amt, excess, writer.into_inner())
+    }
+
+    #[test]
+    fn buffered_data_after_limit_kept() {
+        let (amt, excess, written) = copy(vec![b"head", b"er+b", b"ody", b"more"], 6);
+        assert_eq!(amt, 6);
+        assert_eq!(written, b"header");
+        assert_eq!(excess, b"+bodymore");
+    }
+
+    #[test]
+    fn eof_before_limit() {
+        let (amt, excess, written) = copy(vec![b"short", b""], 10);
+        assert_eq!(amt, 5);
+        assert_eq!(written, b"short");
+        assert!(excess.is_empty());
+    }
+}
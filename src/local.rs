@@ -0,0 +1,52 @@
+use std::io;
+use std::thread::{self, ThreadId};
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use {copy_with_buffer, Copy};
+
+/// A future which copies data like [`Copy`] and checks that it stays on the
+/// thread it was created on.
+///
+/// Created by the [`copy_local`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_local`]: fn.copy_local.html
+#[derive(Debug)]
+pub struct CopyLocal<R, W> {
+    inner: Copy<R, W>,
+    thread: ThreadId,
+}
+
+/// Variant of [`copy_with_buffer`] meant for `!Send` readers and writers.
+///
+/// In debug builds, polling the future on a different thread than the one
+/// which called `copy_local` panics. This catches futures which were moved to
+/// another thread by mistake, e.g. by a wrapper which sidesteps the `Send` check.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_local<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyLocal<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyLocal {
+        inner: copy_with_buffer(reader, writer, buffer),
+        thread: thread::current().id(),
+    }
+}
+
+impl<R, W> Future for CopyLocal<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        debug_assert_eq!(thread::current().id(), self.thread,
+                         "CopyLocal polled on a different thread than it was created on");
+        self.inner.poll()
+    }
+}
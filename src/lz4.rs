@@ -0,0 +1,303 @@
+use std::io::{self, Read, Write};
+
+use futures::{Future, Poll};
+
+use lz4_flex::block::get_maximum_output_size;
+use lz4_flex::frame::{FrameDecoder, FrameEncoder};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data from a reader into a writer, compressing each
+/// chunk into its own length-prefixed LZ4 frame.
+///
+/// Created by the [`copy_lz4_framed`] function.
+///
+/// [`copy_lz4_framed`]: fn.copy_lz4_framed.html
+#[derive(Debug)]
+pub struct CopyLz4Framed<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    out: Vec<u8>,
+    pos: usize,
+    amt: u64,
+    compressed: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for protocols where each message must be
+/// decompressible on its own.
+///
+/// Each chunk read is compressed into a complete LZ4 frame, which is written
+/// as a 4-byte big-endian length followed by the frame. [`copy_lz4_unframed`]
+/// does the reverse.
+///
+/// On success the number of bytes read is followed by the number of bytes
+/// written, length prefixes included.
+///
+/// Available with the `lz4` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`copy_lz4_unframed`]: fn.copy_lz4_unframed.html
+pub fn copy_lz4_framed<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyLz4Framed<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyLz4Framed {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        out: Vec::new(),
+        pos: 0,
+        amt: 0,
+        compressed: 0,
+        buffer: Some(buffer),
+    }
+}
+
+fn compress_frame(data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    out.clear();
+    out.extend_from_slice(&[0; 4]);
+    let mut encoder = FrameEncoder::new(&mut *out);
+    encoder.write_all(data)?;
+    encoder.finish()?;
+    let len = out.len() - 4;
+    if len > u32::MAX as usize {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "LZ4 frame too large"));
+    }
+    out[..4].copy_from_slice(&(len as u32).to_be_bytes());
+    Ok(())
+}
+
+impl<R, W> Future for CopyLz4Framed<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If the last frame is written, then we need to read some data
+            // to continue, and compress it.
+            if self.pos == self.out.len() && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    compress_frame(&buf[..n], &mut self.out)?;
+                    self.pos = 0;
+                    self.amt += n as u64;
+                }
+            }
+
+            // If we have a frame, let's write it out!
+            while self.pos < self.out.len() {
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&self.out[self.pos..]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.compressed += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.out.len() && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, self.compressed, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+/// A future which decompresses length-prefixed LZ4 frames from a reader
+/// into a writer.
+///
+/// Created by the [`copy_lz4_unframed`] function.
+///
+/// [`copy_lz4_unframed`]: fn.copy_lz4_unframed.html
+#[derive(Debug)]
+pub struct CopyLz4Unframed<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    input: Vec<u8>,
+    out: Vec<u8>,
+    pos: usize,
+    amt: u64,
+    compressed: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Reverse of [`copy_lz4_framed`]: reads frames written by it, decompresses
+/// each one and writes the data out.
+///
+/// Each frame has to decompress into at most `buffer.len()` bytes, so
+/// `buffer` should be at least as large as the one given to
+/// [`copy_lz4_framed`]. Fails with `InvalidData` if a frame does not decode
+/// or is too large, and with `UnexpectedEof` if the reader ends in the middle
+/// of a frame.
+/// On success the number of bytes written is followed by the number of bytes
+/// read, length prefixes included.
+///
+/// Available with the `lz4` feature.
+///
+/// [`copy_lz4_framed`]: fn.copy_lz4_framed.html
+pub fn copy_lz4_unframed<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyLz4Unframed<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyLz4Unframed {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        input: Vec::new(),
+        out: Vec::new(),
+        pos: 0,
+        amt: 0,
+        compressed: 0,
+        buffer: Some(buffer),
+    }
+}
+
+/// Upper bound of the size of an LZ4 frame holding `data_len` bytes: the
+/// frame header, end mark and content checksum, plus the size, checksum and
+/// worst case expansion of each 64 KiB block.
+fn max_frame_len(data_len: usize) -> usize {
+    let blocks = data_len / (64 * 1024) + 1;
+    get_maximum_output_size(data_len) + 27 + blocks * 28
+}
+
+/// Decompresses the first frame of `input` into `out`, if it is complete.
+/// Frames which can't decompress into at most `max` bytes are rejected.
+fn decompress_frame(input: &mut Vec<u8>, out: &mut Vec<u8>, max: usize) -> io::Result<bool> {
+    if input.len() < 4 {
+        return Ok(false);
+    }
+    let len = u32::from_be_bytes([input[0], input[1], input[2], input[3]]) as usize;
+    if len > max_frame_len(max) {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  "LZ4 frame larger than the buffer"));
+    }
+    if input.len() < 4 + len {
+        return Ok(false);
+    }
+    out.clear();
+    let decoder = FrameDecoder::new(&input[4..4 + len]);
+    decoder.take(max as u64 + 1).read_to_end(out).map_err(|e| {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    })?;
+    if out.len() > max {
+        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                  "LZ4 frame decompresses to more than the buffer"));
+    }
+    input.drain(..4 + len);
+    Ok(true)
+}
+
+impl<R, W> Future for CopyLz4Unframed<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If the last frame is written, decode the next one, reading
+            // more data if it's not all here yet.
+            if self.pos == self.out.len() {
+                let max = self.buffer.as_ref().unwrap().len();
+                if decompress_frame(&mut self.input, &mut self.out, max)? {
+                    self.pos = 0;
+                } else if !self.read_done {
+                    let buf = self.buffer.as_mut().unwrap();
+                    let reader = self.reader.as_mut().unwrap();
+                    let n = try_nb!(reader.read(buf));
+                    if n == 0 {
+                        self.read_done = true;
+                    } else {
+                        self.input.extend_from_slice(&buf[..n]);
+                        self.compressed += n as u64;
+                    }
+                    continue;
+                }
+            }
+
+            // If we have decompressed data, let's write it out!
+            while self.pos < self.out.len() {
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&self.out[self.pos..]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.out.len() && self.read_done {
+                if !self.input.is_empty() {
+                    return Err(io::Error::new(io::ErrorKind::UnexpectedEof,
+                                              "reader ended in the middle of a frame"));
+                }
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, self.compressed, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use futures::Future;
+
+    use super::*;
+
+    fn unframe(input: Vec<u8>, buffer_len: usize) -> io::Result<Vec<u8>> {
+        copy_lz4_unframed(Cursor::new(input), Cursor::new(Vec::new()),
+                          vec![0; buffer_len].into_boxed_slice())
+            .wait()
+            .map(|(_, _, _, out, _)| out.into_inner())
+    }
+
+    #[test]
+    fn round_trip() {
+        let data: Vec<u8> = (0..10000).map(|i| (i % 251) as u8).collect();
+        let (_, _, _, framed, _) = copy_lz4_framed(Cursor::new(data.clone()),
+                                                   Cursor::new(Vec::new()),
+                                                   vec![0; 1000].into_boxed_slice())
+            .wait().unwrap();
+        assert_eq!(unframe(framed.into_inner(), 1000).unwrap(), data);
+    }
+
+    #[test]
+    fn oversized_frames() {
+        // A length prefix beyond any frame for the buffer fails right away.
+        let e = unframe(vec![0xFF, 0xFF, 0xFF, 0xFF, 0], 1000).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+        // So does a frame that decompresses to more than the buffer.
+        let mut framed = Vec::new();
+        compress_frame(&[0; 2000], &mut framed).unwrap();
+        let e = unframe(framed, 1000).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -0,0 +1,176 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], writing only chunks of at least
+/// a given size.
+///
+/// Created by the [`copy_with_min_read`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_min_read`]: fn.copy_with_min_read.html
+#[derive(Debug)]
+pub struct CopyMinRead<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    acc: usize,
+    amt: u64,
+    min_read: usize,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which keeps reading into the rest of the
+/// buffer until at least `min_read` bytes are gathered, and only then writes
+/// them as one chunk.
+///
+/// Only the last chunk before EOF may be shorter. This saves write calls when
+/// the reader returns many small pieces.
+///
+/// # Panics
+///
+/// Panics if `min_read` is larger than the buffer.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_min_read<R, W>(reader: R, writer: W, buffer: Box<[u8]>, min_read: usize)
+    -> CopyMinRead<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(min_read <= buffer.len(), "min_read is larger than the buffer");
+    CopyMinRead {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        acc: 0,
+        min_read,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyMinRead<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // Until we have gathered enough data, read some more behind what
+            // we already have.
+            if !self.read_done && (self.acc == 0 || self.acc < self.min_read) {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(&mut buf[self.acc..]));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.acc += n;
+                }
+                continue;
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.acc {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.acc]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+            self.pos = 0;
+            self.acc = 0;
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cmp;
+    use std::io::{self, Cursor, Read};
+
+    use futures::task;
+
+    use super::*;
+
+    /// Hands out at most 3 bytes per read, not being ready every other time.
+    #[derive(Debug)]
+    struct Trickle {
+        inner: Cursor<Vec<u8>>,
+        ready: bool,
+    }
+
+    impl Read for Trickle {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.ready = !self.ready;
+            if !self.ready {
+                task::current().notify();
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+            let len = cmp::min(buf.len(), 3);
+            self.inner.read(&mut buf[..len])
+        }
+    }
+
+    impl AsyncRead for Trickle {}
+
+    /// Records the length of every write.
+    #[derive(Debug)]
+    struct Chunks(Vec<usize>);
+
+    impl io::Write for Chunks {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(buf.len());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Chunks {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    #[test]
+    fn small_reads_gathered() {
+        let reader = Trickle { inner: Cursor::new(vec![1; 25]), ready: false };
+        let (amt, _, writer, _) = copy_with_min_read(reader, Chunks(Vec::new()),
+                                                     vec![0; 16].into_boxed_slice(), 8)
+            .wait()
+            .unwrap();
+        assert_eq!(amt, 25);
+        assert_eq!(writer.0, [9, 9, 7]);
+    }
+
+    #[test]
+    #[should_panic(expected = "min_read is larger than the buffer")]
+    fn min_read_larger_than_buffer() {
+        copy_with_min_read(Cursor::new(Vec::new()), Chunks(Vec::new()),
+                           vec![0; 4].into_boxed_slice(), 5);
+    }
+}
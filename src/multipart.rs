@@ -0,0 +1,238 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data from a reader into a series of writers, one
+/// per part.
+///
+/// Created by the [`copy_multipart`] function.
+///
+/// [`copy_multipart`]: fn.copy_multipart.html
+pub struct CopyMultipart<R, F, W, Fut> {
+    reader: Option<R>,
+    read_done: bool,
+    factory: F,
+    writer_fut: Option<Fut>,
+    writer: Option<W>,
+    part_size: u64,
+    part_written: u64,
+    parts: u32,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for multi-part uploads, where each part
+/// has to be sent through its own writer, like a separate PUT request.
+///
+/// Whenever there is data for a new part, `factory` is called with the part
+/// number, starting at 0, and the returned future is driven to get the
+/// writer. Once `part_size` bytes are written to it, or the reader hits EOF,
+/// the writer is flushed and dropped. Only the last part may be shorter, and
+/// empty input creates no parts.
+///
+/// On success the number of bytes written is followed by the number of parts.
+///
+/// # Panics
+///
+/// Panics if `part_size` is zero.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_multipart<R, F, W, Fut>(reader: R, factory: F, buffer: Box<[u8]>, part_size: u64)
+    -> CopyMultipart<R, F, W, Fut>
+    where R: AsyncRead,
+          F: FnMut(usize) -> Fut,
+          Fut: Future<Item = W, Error = io::Error>,
+          W: AsyncWrite,
+{
+    assert!(part_size > 0, "part size must not be zero");
+    CopyMultipart {
+        reader: Some(reader),
+        read_done: false,
+        factory,
+        writer_fut: None,
+        writer: None,
+        part_size,
+        part_written: 0,
+        parts: 0,
+        pos: 0,
+        cap: 0,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R: fmt::Debug, F, W: fmt::Debug, Fut> fmt::Debug for CopyMultipart<R, F, W, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyMultipart")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("writer_pending", &self.writer_fut.is_some())
+            .field("part_size", &self.part_size)
+            .field("part_written", &self.part_written)
+            .field("parts", &self.parts)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, F, W, Fut> Future for CopyMultipart<R, F, W, Fut>
+    where R: AsyncRead,
+          F: FnMut(usize) -> Fut,
+          Fut: Future<Item = W, Error = io::Error>,
+          W: AsyncWrite,
+{
+    type Item = (u64, u32, R, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, u32, R, Box<[u8]>), io::Error> {
+        loop {
+            // If the current part is complete, flush out its writer and let
+            // it go.
+            let part_done = self.part_written == self.part_size
+                || (self.pos == self.cap && self.read_done);
+            if part_done && self.writer.is_some() {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                self.writer = None;
+            }
+
+            // If we've written all the data and we've seen EOF, finish the
+            // transfer.
+            if self.pos == self.cap && self.read_done {
+                let reader = self.reader.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, self.parts, reader, buffer).into())
+            }
+
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+                continue;
+            }
+
+            // We have data for a new part, so we need a writer for it.
+            if self.writer.is_none() {
+                if self.writer_fut.is_none() {
+                    self.writer_fut = Some((self.factory)(self.parts as usize));
+                }
+                let writer = try_ready!(self.writer_fut.as_mut().unwrap().poll());
+                self.writer_fut = None;
+                self.writer = Some(writer);
+                self.part_written = 0;
+                self.parts += 1;
+            }
+
+            // If our buffer has some data, let's write as much of it as fits
+            // into the current part!
+            let left = cmp::min(self.part_size - self.part_written, usize::MAX as u64) as usize;
+            let end = cmp::min(self.cap, self.pos.saturating_add(left));
+            let buf = self.buffer.as_mut().unwrap();
+            let writer = self.writer.as_mut().unwrap();
+            let i = try_nb!(writer.write(&buf[self.pos..end]));
+            if i == 0 {
+                return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                          "write zero byte into writer"));
+            } else {
+                self.pos += i;
+                self.amt += i as u64;
+                self.part_written += i as u64;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::io::{self, Cursor};
+    use std::rc::Rc;
+
+    use futures::future::{self, FutureResult};
+
+    use super::*;
+
+    /// Contents of each part and whether it got flushed.
+    type Parts = Rc<RefCell<Vec<(Vec<u8>, bool)>>>;
+
+    struct Part {
+        index: usize,
+        parts: Parts,
+    }
+
+    impl io::Write for Part {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let mut parts = self.parts.borrow_mut();
+            assert!(!parts[self.index].1, "write after flush");
+            parts[self.index].0.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.parts.borrow_mut()[self.index].1 = true;
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Part {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    fn copy(data: Vec<u8>, part_size: u64) -> (u64, u32, Vec<(Vec<u8>, bool)>) {
+        let parts = Parts::default();
+        let factory = |index| -> FutureResult<Part, io::Error> {
+            assert_eq!(index, parts.borrow().len());
+            parts.borrow_mut().push((Vec::new(), false));
+            future::ok(Part { index, parts: parts.clone() })
+        };
+        let (amt, count, _, _) = copy_multipart(Cursor::new(data), factory,
+                                                vec![0; 7].into_boxed_slice(), part_size)
+            .wait()
+            .unwrap();
+        let parts = parts.borrow().clone();
+        (amt, count, parts)
+    }
+
+    #[test]
+    fn split_into_parts() {
+        let data: Vec<u8> = (0..25).collect();
+        let (amt, count, parts) = copy(data.clone(), 10);
+        assert_eq!((amt, count), (25, 3));
+        assert_eq!(parts, [(data[..10].to_vec(), true),
+                           (data[10..20].to_vec(), true),
+                           (data[20..].to_vec(), true)]);
+    }
+
+    #[test]
+    fn exact_multiple_of_part_size() {
+        let (amt, count, parts) = copy(vec![1; 20], 10);
+        assert_eq!((amt, count), (20, 2));
+        assert!(parts.iter().all(|&(ref part, flushed)| part.len() == 10 && flushed));
+    }
+
+    #[test]
+    fn empty_input_creates_no_parts() {
+        let (amt, count, parts) = copy(Vec::new(), 10);
+        assert_eq!((amt, count), (0, 0));
+        assert!(parts.is_empty());
+    }
+}
@@ -0,0 +1,151 @@
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+use futures::{Future, Poll};
+
+use libc;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use socket::{get_int_option, set_int_option};
+
+/// A future which copies data like [`Copy`] into a TCP socket, batching
+/// writes with Nagle's algorithm until EOF.
+///
+/// Created by the [`copy_with_nagle_toggle`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_nagle_toggle`]: fn.copy_with_nagle_toggle.html
+#[derive(Debug)]
+pub struct CopyNagleToggle<R, W: AsRawFd> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    started: bool,
+    original_nodelay: Option<libc::c_int>,
+    nodelay_set: bool,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for bulk transfers into a TCP socket.
+///
+/// When first polled, `TCP_NODELAY` of `writer` is cleared so that Nagle's
+/// algorithm batches small writes. Once EOF is read, `TCP_NODELAY` is set
+/// before the final flush, so the last segment goes out without delay.
+/// The original setting is restored when the copy finishes or is dropped.
+///
+/// If `writer` is not a socket, it is left alone.
+///
+/// Available on Unix.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_nagle_toggle<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> CopyNagleToggle<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite + AsRawFd,
+{
+    CopyNagleToggle {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        started: false,
+        original_nodelay: None,
+        nodelay_set: false,
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W: AsRawFd> CopyNagleToggle<R, W> {
+    fn set_nodelay(&self, value: libc::c_int) -> io::Result<()> {
+        match self.original_nodelay {
+            Some(_) => {
+                let fd = self.writer.as_ref().unwrap().as_raw_fd();
+                set_int_option(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY, value)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        if let Some(original) = self.original_nodelay {
+            self.set_nodelay(original)?;
+            self.original_nodelay = None;
+        }
+        Ok(())
+    }
+}
+
+impl<R, W> Future for CopyNagleToggle<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite + AsRawFd,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        if !self.started {
+            let fd = self.writer.as_ref().unwrap().as_raw_fd();
+            self.original_nodelay = get_int_option(fd, libc::IPPROTO_TCP, libc::TCP_NODELAY)?;
+            self.set_nodelay(0)?;
+            self.started = true;
+        }
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, push out the
+            // last segment, flush out the data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                if !self.nodelay_set {
+                    self.set_nodelay(1)?;
+                    self.nodelay_set = true;
+                }
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                self.restore()?;
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+impl<R, W: AsRawFd> Drop for CopyNagleToggle<R, W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            let _ = self.restore();
+        }
+    }
+}
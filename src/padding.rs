@@ -0,0 +1,119 @@
+use std::cmp;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+static ZEROES: [u8; 4096] = [0; 4096];
+
+/// A future which copies data like [`Copy`] and then pads the output with
+/// zero bytes up to a multiple of some alignment.
+///
+/// Created by the [`copy_with_padding`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_padding`]: fn.copy_with_padding.html
+#[derive(Debug)]
+pub struct CopyWithPadding<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    align: u64,
+    padded: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for block-aligned formats: after all data is
+/// copied, zero bytes are written until the output size is a multiple of
+/// `align`, and only then the writer is flushed.
+///
+/// On success the number of copied bytes is followed by the number of padding
+/// bytes. `align` of 0 or 1 means no padding.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_padding<R, W>(reader: R, writer: W, buffer: Box<[u8]>, align: usize)
+    -> CopyWithPadding<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyWithPadding {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        align: align as u64,
+        padded: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyWithPadding<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, pad the output,
+            // flush out the data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                let pad = if self.align == 0 {
+                    0
+                } else {
+                    (self.align - self.amt % self.align) % self.align
+                };
+                while self.padded < pad {
+                    let writer = self.writer.as_mut().unwrap();
+                    let len = cmp::min(pad - self.padded, ZEROES.len() as u64) as usize;
+                    let i = try_nb!(writer.write(&ZEROES[..len]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    }
+                    self.padded += i as u64;
+                }
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, self.padded, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies all data from a reader into several writers at once.
+///
+/// Created by the [`copy_parallel_multicast`] function.
+///
+/// [`copy_parallel_multicast`]: fn.copy_parallel_multicast.html
+#[derive(Debug)]
+pub struct CopyParallelMulticast<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writers: Option<Vec<W>>,
+    positions: Vec<usize>,
+    flushed: Vec<bool>,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Creates a future which copies all bytes from `reader` into each of
+/// `writers`, like [`copy_with_buffer`] for a single writer.
+///
+/// Every chunk is written to all writers concurrently: a writer which is not
+/// ready does not hold back the others, each keeps its own position within the
+/// chunk. The next chunk is read only after all writers have taken the current
+/// one, so the slowest writer sets the pace. All writers are flushed at the end.
+///
+/// On success the number of bytes read (i.e. written to each writer) is returned
+/// along with the `reader`, the `writers` and the buffer.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_parallel_multicast<R, W>(reader: R, writers: Vec<W>, buffer: Box<[u8]>)
+    -> CopyParallelMulticast<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyParallelMulticast {
+        reader: Some(reader),
+        read_done: false,
+        positions: vec![0; writers.len()],
+        flushed: vec![false; writers.len()],
+        writers: Some(writers),
+        cap: 0,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyParallelMulticast<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, Vec<W>, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, Vec<W>, Box<[u8]>), io::Error> {
+        loop {
+            // If every writer got the whole chunk, then we need to read some
+            // data to continue.
+            let cap = self.cap;
+            if self.positions.iter().all(|&pos| pos == cap) && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    for pos in &mut self.positions {
+                        *pos = 0;
+                    }
+                    self.cap = n;
+                    self.amt += n as u64;
+                }
+            }
+
+            // Give each writer as much of the chunk as it accepts.
+            let mut blocked = false;
+            {
+                let buf = self.buffer.as_ref().unwrap();
+                let writers = self.writers.as_mut().unwrap();
+                for (writer, pos) in writers.iter_mut().zip(self.positions.iter_mut()) {
+                    while *pos < self.cap {
+                        match writer.write(&buf[*pos..self.cap]) {
+                            Ok(0) => {
+                                return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                          "write zero byte into writer"));
+                            }
+                            Ok(i) => *pos += i,
+                            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                                blocked = true;
+                                break;
+                            }
+                            Err(e) => return Err(e),
+                        }
+                    }
+                }
+            }
+            if blocked {
+                return Ok(Async::NotReady);
+            }
+
+            // If all writers have all the data and we've seen EOF, flush them
+            // all and finish the transfer.
+            if self.read_done {
+                let writers = self.writers.as_mut().unwrap();
+                for (writer, flushed) in writers.iter_mut().zip(self.flushed.iter_mut()) {
+                    if *flushed {
+                        continue;
+                    }
+                    match writer.flush() {
+                        Ok(()) => *flushed = true,
+                        Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => blocked = true,
+                        Err(e) => return Err(e),
+                    }
+                }
+                if blocked {
+                    return Ok(Async::NotReady);
+                }
+                let reader = self.reader.take().unwrap();
+                let writers = self.writers.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writers, buffer).into())
+            }
+        }
+    }
+}
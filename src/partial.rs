@@ -0,0 +1,121 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use {copy_with_buffer, Copy};
+
+type Finished<R, W> = (u64, R, W, Box<[u8]>);
+type Stripped<R, W> = io::Result<(u64, R, W)>;
+
+/// Error of [`copy_partial`], giving back everything the copy owned.
+///
+/// [`copy_partial`]: fn.copy_partial.html
+pub struct PartialCopyError<R, W> {
+    /// Number of bytes successfully written before the error.
+    pub amt: u64,
+    /// The reader.
+    pub reader: R,
+    /// The writer.
+    pub writer: W,
+    /// The copy buffer.
+    pub buffer: Box<[u8]>,
+    /// The underlying error.
+    pub error: io::Error,
+}
+
+impl<R, W> fmt::Debug for PartialCopyError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PartialCopyError")
+            .field("amt", &self.amt)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<R, W> fmt::Display for PartialCopyError<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "copy failed after {} bytes: {}", self.amt, self.error)
+    }
+}
+
+impl<R, W> Error for PartialCopyError<R, W> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+impl<R, W> From<PartialCopyError<R, W>> for io::Error {
+    fn from(e: PartialCopyError<R, W>) -> io::Error {
+        e.error
+    }
+}
+
+/// A future which copies data like [`Copy`], but gives the reader, writer and
+/// buffer back on error too.
+///
+/// Created by the [`copy_partial`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_partial`]: fn.copy_partial.html
+#[derive(Debug)]
+pub struct CopyPartial<R, W> {
+    inner: Copy<R, W>,
+}
+
+/// Variant of [`copy_with_buffer`] failing with [`PartialCopyError`], so that
+/// nothing is lost when the copy fails. See also [`extract_buffer`].
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`PartialCopyError`]: struct.PartialCopyError.html
+/// [`extract_buffer`]: fn.extract_buffer.html
+pub fn copy_partial<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyPartial<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyPartial {
+        inner: copy_with_buffer(reader, writer, buffer),
+    }
+}
+
+impl<R, W> Future for CopyPartial<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = PartialCopyError<R, W>;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), PartialCopyError<R, W>> {
+        match self.inner.poll() {
+            Ok(x) => Ok(x),
+            Err(error) => Err(PartialCopyError {
+                amt: self.inner.amt,
+                reader: self.inner.reader.take().unwrap(),
+                writer: self.inner.writer.take().unwrap(),
+                buffer: self.inner.buffer.take().unwrap(),
+                error,
+            }),
+        }
+    }
+}
+
+/// Splits the result of [`copy_partial`] into the buffer, which is there in
+/// both cases, and the rest of the result.
+///
+/// This lets a buffer taken from a [`BufferPool`] be checked back in
+/// regardless of the outcome.
+///
+/// [`copy_partial`]: fn.copy_partial.html
+/// [`BufferPool`]: struct.BufferPool.html
+pub fn extract_buffer<R, W>(result: Result<Finished<R, W>, PartialCopyError<R, W>>)
+    -> (Option<Box<[u8]>>, Stripped<R, W>)
+{
+    match result {
+        Ok((amt, reader, writer, buffer)) => (Some(buffer), Ok((amt, reader, writer))),
+        Err(e) => (Some(e.buffer), Err(e.error)),
+    }
+}
+
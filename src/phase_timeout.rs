@@ -0,0 +1,314 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+
+/// Time limits of [`copy_multi_phase_timeout`], one per kind of operation.
+///
+/// [`copy_multi_phase_timeout`]: fn.copy_multi_phase_timeout.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MultiPhaseTimeout {
+    /// Limit for waiting for one chunk from the reader.
+    pub read: Duration,
+    /// Limit for writing out one chunk.
+    pub write: Duration,
+    /// Limit for the final flush.
+    pub flush: Duration,
+}
+
+/// Error of [`copy_multi_phase_timeout`], telling which phase timed out.
+///
+/// Each timeout variant holds the limit that was exceeded.
+///
+/// [`copy_multi_phase_timeout`]: fn.copy_multi_phase_timeout.html
+#[derive(Debug)]
+pub enum CopyPhaseTimeout {
+    /// Reading a chunk took too long.
+    Read(Duration),
+    /// Writing a chunk took too long.
+    Write(Duration),
+    /// The final flush took too long.
+    Flush(Duration),
+    /// Any other I/O error.
+    Io(io::Error),
+}
+
+impl fmt::Display for CopyPhaseTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CopyPhaseTimeout::Read(limit) => write!(f, "read timed out after {:?}", limit),
+            CopyPhaseTimeout::Write(limit) => write!(f, "write timed out after {:?}", limit),
+            CopyPhaseTimeout::Flush(limit) => write!(f, "flush timed out after {:?}", limit),
+            CopyPhaseTimeout::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for CopyPhaseTimeout {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            CopyPhaseTimeout::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for CopyPhaseTimeout {
+    fn from(e: io::Error) -> CopyPhaseTimeout {
+        CopyPhaseTimeout::Io(e)
+    }
+}
+
+impl From<CopyPhaseTimeout> for io::Error {
+    fn from(e: CopyPhaseTimeout) -> io::Error {
+        match e {
+            CopyPhaseTimeout::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::TimedOut, e),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Phase {
+    Read,
+    Write,
+    Flush,
+}
+
+/// A future which copies data like [`Copy`], with separate time limits for
+/// reading, writing and flushing.
+///
+/// Created by the [`copy_multi_phase_timeout`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_multi_phase_timeout`]: fn.copy_multi_phase_timeout.html
+#[derive(Debug)]
+pub struct CopyMultiPhaseTimeout<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    timeouts: MultiPhaseTimeout,
+    phase: Option<Phase>,
+    sleep: Delay,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which restarts a timer whenever it starts
+/// waiting for a chunk to be read, starts writing a chunk out or starts the
+/// final flush, each with its own limit from `timeouts`.
+///
+/// When a limit is exceeded, the copy fails with the matching
+/// [`CopyPhaseTimeout`] variant. Needs to be run within a `tokio-timer`
+/// timer context.
+///
+/// Available with the `tokio-timer` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`CopyPhaseTimeout`]: enum.CopyPhaseTimeout.html
+pub fn copy_multi_phase_timeout<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                      timeouts: MultiPhaseTimeout)
+    -> CopyMultiPhaseTimeout<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyMultiPhaseTimeout {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        timeouts,
+        phase: None,
+        sleep: Delay::new(Instant::now() + timeouts.read),
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> CopyMultiPhaseTimeout<R, W> {
+    /// Starts the timer if `phase` is new and checks it.
+    fn check_phase(&mut self, phase: Phase) -> Result<(), CopyPhaseTimeout> {
+        let limit = match phase {
+            Phase::Read => self.timeouts.read,
+            Phase::Write => self.timeouts.write,
+            Phase::Flush => self.timeouts.flush,
+        };
+        if self.phase != Some(phase) {
+            self.sleep.reset(Instant::now() + limit);
+            self.phase = Some(phase);
+        }
+        match self.sleep.poll() {
+            Ok(Async::Ready(())) => Err(match phase {
+                Phase::Read => CopyPhaseTimeout::Read(limit),
+                Phase::Write => CopyPhaseTimeout::Write(limit),
+                Phase::Flush => CopyPhaseTimeout::Flush(limit),
+            }),
+            Ok(Async::NotReady) => Ok(()),
+            Err(e) => Err(io::Error::other(e).into()),
+        }
+    }
+}
+
+impl<R, W> Future for CopyMultiPhaseTimeout<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = CopyPhaseTimeout;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), CopyPhaseTimeout> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                self.check_phase(Phase::Read)?;
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+                self.phase = None;
+            }
+
+            // If our buffer has some data, let's write it out!
+            if self.pos < self.cap {
+                self.check_phase(Phase::Write)?;
+                while self.pos < self.cap {
+                    let buf = self.buffer.as_mut().unwrap();
+                    let writer = self.writer.as_mut().unwrap();
+                    let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer").into());
+                    } else {
+                        self.pos += i;
+                        self.amt += i as u64;
+                    }
+                }
+                self.phase = None;
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                self.check_phase(Phase::Flush)?;
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read, Write};
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    /// Reads from or writes into a `Cursor`, but never gets ready for the
+    /// stalled operation.
+    #[derive(Debug, Default)]
+    struct Stall {
+        inner: Cursor<Vec<u8>>,
+        read: bool,
+        write: bool,
+        flush: bool,
+    }
+
+    fn stalled() -> io::Error {
+        io::ErrorKind::WouldBlock.into()
+    }
+
+    impl Read for Stall {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.read { Err(stalled()) } else { self.inner.read(buf) }
+        }
+    }
+
+    impl AsyncRead for Stall {}
+
+    impl Write for Stall {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.write { Err(stalled()) } else { self.inner.write(buf) }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            if self.flush { Err(stalled()) } else { Ok(()) }
+        }
+    }
+
+    impl AsyncWrite for Stall {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    fn copy(reader: Stall, writer: Stall, timeouts: MultiPhaseTimeout)
+        -> Result<u64, CopyPhaseTimeout>
+    {
+        let copy = copy_multi_phase_timeout(reader, writer, vec![0; 4].into_boxed_slice(),
+                                            timeouts);
+        Runtime::new().unwrap().block_on(copy).map(|(amt, ..)| amt)
+    }
+
+    fn timeouts(read: u64, write: u64, flush: u64) -> MultiPhaseTimeout {
+        MultiPhaseTimeout {
+            read: Duration::from_millis(read),
+            write: Duration::from_millis(write),
+            flush: Duration::from_millis(flush),
+        }
+    }
+
+    fn input() -> Stall {
+        Stall { inner: Cursor::new(vec![1; 10]), ..Stall::default() }
+    }
+
+    #[test]
+    fn completes_in_time() {
+        assert_eq!(copy(input(), Stall::default(), timeouts(50, 50, 50)).unwrap(), 10);
+    }
+
+    #[test]
+    fn read_timeout() {
+        let reader = Stall { read: true, ..Stall::default() };
+        match copy(reader, Stall::default(), timeouts(50, 10_000, 10_000)) {
+            Err(CopyPhaseTimeout::Read(limit)) => assert_eq!(limit, Duration::from_millis(50)),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn write_timeout() {
+        let writer = Stall { write: true, ..Stall::default() };
+        match copy(input(), writer, timeouts(10_000, 50, 10_000)) {
+            Err(CopyPhaseTimeout::Write(limit)) => assert_eq!(limit, Duration::from_millis(50)),
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn flush_timeout() {
+        let writer = Stall { flush: true, ..Stall::default() };
+        match copy(input(), writer, timeouts(10_000, 10_000, 50)) {
+            Err(CopyPhaseTimeout::Flush(limit)) => assert_eq!(limit, Duration::from_millis(50)),
+            r => panic!("{:?}", r),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use {copy_with_buffer, Copy};
+
+/// A shared pool of equally sized copy buffers.
+///
+/// Cloning the pool is cheap and gives another handle to the same buffers.
+#[derive(Debug, Clone)]
+pub struct BufferPool {
+    buffer_size: usize,
+    buffers: Arc<Mutex<Vec<Box<[u8]>>>>,
+}
+
+impl BufferPool {
+    /// Creates an empty pool handing out buffers of `buffer_size` bytes.
+    pub fn new(buffer_size: usize) -> BufferPool {
+        BufferPool {
+            buffer_size,
+            buffers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Takes a buffer from the pool, allocating a new one if it's empty.
+    pub fn checkout(&self) -> Box<[u8]> {
+        self.buffers.lock().unwrap().pop()
+            .unwrap_or_else(|| vec![0; self.buffer_size].into_boxed_slice())
+    }
+
+    /// Returns a buffer to the pool. Buffers of a different size are dropped.
+    pub fn checkin(&self, buffer: Box<[u8]>) {
+        if buffer.len() == self.buffer_size {
+            self.buffers.lock().unwrap().push(buffer);
+        }
+    }
+
+    /// Starts a [`copy_with_buffer`] using a buffer from the pool, which goes
+    /// back to the pool once the copy finishes, fails or is dropped.
+    ///
+    /// [`copy_with_buffer`]: fn.copy_with_buffer.html
+    pub fn start_copy<R, W>(&self, reader: R, writer: W) -> PooledCopy<R, W>
+        where R: AsyncRead,
+              W: AsyncWrite,
+    {
+        PooledCopy {
+            inner: copy_with_buffer(reader, writer, self.checkout()),
+            pool: self.clone(),
+        }
+    }
+}
+
+/// A future which copies data like [`Copy`] with a buffer borrowed from a
+/// [`BufferPool`].
+///
+/// Created by the [`BufferPool::start_copy`] method.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`BufferPool`]: struct.BufferPool.html
+/// [`BufferPool::start_copy`]: struct.BufferPool.html#method.start_copy
+#[derive(Debug)]
+pub struct PooledCopy<R, W> {
+    inner: Copy<R, W>,
+    pool: BufferPool,
+}
+
+impl<R, W> Future for PooledCopy<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W), io::Error> {
+        let (amt, reader, writer, buffer) = try_ready!(self.inner.poll());
+        self.pool.checkin(buffer);
+        Ok(Async::Ready((amt, reader, writer)))
+    }
+}
+
+impl<R, W> Drop for PooledCopy<R, W> {
+    fn drop(&mut self) {
+        if let Some(buffer) = self.inner.buffer.take() {
+            self.pool.checkin(buffer);
+        }
+    }
+}
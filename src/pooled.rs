@@ -0,0 +1,128 @@
+use std::io;
+
+use futures::{Future, Poll, Stream};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], taking a fresh buffer from a
+/// stream for every chunk.
+///
+/// Created by the [`copy_pooled`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_pooled`]: fn.copy_pooled.html
+#[derive(Debug)]
+pub struct CopyPooled<R, W, S> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    buffer_stream: S,
+    stream_done: bool,
+    need_buffer: bool,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for buffer pools from other crates which
+/// hand out buffers through a stream.
+///
+/// A buffer is taken from `buffer_stream` before the first read and after
+/// each chunk is written out; the previous one is dropped, so the pool can
+/// manage its lifecycle, e.g. by recycling buffers on drop. Once the stream
+/// ends, the last buffer is reused. The last buffer is returned on success.
+///
+/// Fails with `InvalidInput` if the stream gives an empty buffer, or ends
+/// before giving any.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_pooled<R, W, S>(buffer_stream: S, reader: R, writer: W) -> CopyPooled<R, W, S>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          S: Stream<Item = Box<[u8]>, Error = io::Error>,
+{
+    CopyPooled {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        buffer_stream,
+        stream_done: false,
+        need_buffer: true,
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: None,
+    }
+}
+
+impl<R, W, S> Future for CopyPooled<R, W, S>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          S: Stream<Item = Box<[u8]>, Error = io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to get a new one and read
+            // some data to continue.
+            if self.pos == self.cap && !self.read_done {
+                if self.need_buffer && !self.stream_done {
+                    match try_ready!(self.buffer_stream.poll()) {
+                        Some(ref buffer) if buffer.is_empty() => {
+                            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                                      "buffer stream gave an empty buffer"));
+                        }
+                        Some(buffer) => self.buffer = Some(buffer),
+                        None => self.stream_done = true,
+                    }
+                }
+                self.need_buffer = false;
+
+                let buf = match self.buffer {
+                    Some(ref mut buf) => buf,
+                    None => return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                                      "buffer stream ended without a buffer")),
+                };
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+                if self.pos == self.cap {
+                    self.need_buffer = true;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
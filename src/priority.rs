@@ -0,0 +1,285 @@
+use std::cmp::{Ordering, Reverse};
+use std::collections::BinaryHeap;
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use futures::sync::oneshot;
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+struct Waiter<W> {
+    priority: u8,
+    seq: u64,
+    sender: oneshot::Sender<W>,
+}
+
+impl<W> Waiter<W> {
+    fn key(&self) -> (u8, Reverse<u64>) {
+        (self.priority, Reverse(self.seq))
+    }
+}
+
+impl<W> PartialEq for Waiter<W> {
+    fn eq(&self, other: &Waiter<W>) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl<W> Eq for Waiter<W> {}
+
+impl<W> PartialOrd for Waiter<W> {
+    fn partial_cmp(&self, other: &Waiter<W>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<W> Ord for Waiter<W> {
+    fn cmp(&self, other: &Waiter<W>) -> Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+struct Shared<W> {
+    writer: Option<W>,
+    waiters: BinaryHeap<Waiter<W>>,
+    next_seq: u64,
+}
+
+/// A writer shared by several [`copy_with_priority`] futures, which get to
+/// write in order of their priority.
+///
+/// Cloning the queue gives another handle to the same writer.
+///
+/// [`copy_with_priority`]: fn.copy_with_priority.html
+pub struct PriorityQueue<W> {
+    shared: Arc<Mutex<Shared<W>>>,
+}
+
+impl<W> Clone for PriorityQueue<W> {
+    fn clone(&self) -> PriorityQueue<W> {
+        PriorityQueue { shared: self.shared.clone() }
+    }
+}
+
+impl<W> fmt::Debug for PriorityQueue<W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let shared = self.shared.lock().unwrap();
+        f.debug_struct("PriorityQueue")
+            .field("busy", &shared.writer.is_none())
+            .field("waiters", &shared.waiters.len())
+            .finish()
+    }
+}
+
+impl<W> PriorityQueue<W> {
+    /// Creates a queue around `writer`.
+    pub fn new(writer: W) -> PriorityQueue<W> {
+        PriorityQueue {
+            shared: Arc::new(Mutex::new(Shared {
+                writer: Some(writer),
+                waiters: BinaryHeap::new(),
+                next_seq: 0,
+            })),
+        }
+    }
+
+    /// Asks for the writer. It is handed over right away if idle, otherwise
+    /// once all waiters with a higher priority, and those with the same
+    /// priority which asked earlier, are done.
+    fn acquire(&self, priority: u8) -> oneshot::Receiver<W> {
+        let (sender, receiver) = oneshot::channel();
+        let mut shared = self.shared.lock().unwrap();
+        match shared.writer.take() {
+            Some(writer) => {
+                let _ = sender.send(writer);
+            }
+            None => {
+                let seq = shared.next_seq;
+                shared.next_seq += 1;
+                shared.waiters.push(Waiter { priority, seq, sender });
+            }
+        }
+        receiver
+    }
+
+    /// Gives the writer to the next waiter, or keeps it if there is none.
+    fn release(&self, mut writer: W) {
+        let mut shared = self.shared.lock().unwrap();
+        while let Some(waiter) = shared.waiters.pop() {
+            match waiter.sender.send(writer) {
+                Ok(()) => return,
+                Err(w) => writer = w,
+            }
+        }
+        shared.writer = Some(writer);
+    }
+}
+
+/// A future which copies data from a reader into a writer shared through a
+/// [`PriorityQueue`].
+///
+/// Created by the [`copy_with_priority`] function.
+///
+/// [`PriorityQueue`]: struct.PriorityQueue.html
+/// [`copy_with_priority`]: fn.copy_with_priority.html
+pub struct CopyPrioritized<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    queue: PriorityQueue<W>,
+    priority: u8,
+    writer: Option<W>,
+    waiting: Option<oneshot::Receiver<W>>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for several copies into one writer, e.g.
+/// a network socket, where higher priority copies should go first.
+///
+/// Before each write and the final flush the copy waits for its turn with the
+/// writer in `queue`, and hands it to the next waiter once the call completes.
+/// A higher `priority` value goes first; equal priorities are served in
+/// order. The writer is kept while it's not ready.
+///
+/// On success, the amount copied is returned along with the reader and the
+/// buffer; the writer stays in the queue.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_priority<R, W>(reader: R, queue: PriorityQueue<W>, buffer: Box<[u8]>,
+                                priority: u8) -> CopyPrioritized<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyPrioritized {
+        reader: Some(reader),
+        read_done: false,
+        queue,
+        priority,
+        writer: None,
+        waiting: None,
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R: fmt::Debug, W> fmt::Debug for CopyPrioritized<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyPrioritized")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("queue", &self.queue)
+            .field("priority", &self.priority)
+            .field("has_writer", &self.writer.is_some())
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W> CopyPrioritized<R, W> {
+    /// Waits for our turn with the writer.
+    fn poll_writer(&mut self) -> Poll<(), io::Error> {
+        if self.writer.is_some() {
+            return Ok(Async::Ready(()));
+        }
+        if self.waiting.is_none() {
+            self.waiting = Some(self.queue.acquire(self.priority));
+        }
+        match self.waiting.as_mut().unwrap().poll() {
+            Ok(Async::Ready(writer)) => {
+                self.waiting = None;
+                self.writer = Some(writer);
+                Ok(Async::Ready(()))
+            }
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(oneshot::Canceled) => Err(io::Error::other("priority queue closed")),
+        }
+    }
+
+    /// Hands the writer to the next waiter, unless the last call would block.
+    fn finish_call<T>(&mut self, res: io::Result<T>) -> Poll<T, io::Error> {
+        match res {
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return Ok(Async::NotReady),
+            _ => {}
+        }
+        self.queue.release(self.writer.take().unwrap());
+        res.map(Async::Ready)
+    }
+}
+
+impl<R, W> Future for CopyPrioritized<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, wait for our turn and write it out!
+            while self.pos < self.cap {
+                try_ready!(self.poll_writer());
+                let res = {
+                    let buf = self.buffer.as_ref().unwrap();
+                    self.writer.as_mut().unwrap().write(&buf[self.pos..self.cap])
+                };
+                let i = try_ready!(self.finish_call(res));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_ready!(self.poll_writer());
+                let res = self.writer.as_mut().unwrap().flush();
+                try_ready!(self.finish_call(res));
+                let reader = self.reader.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, buffer).into())
+            }
+        }
+    }
+}
+
+impl<R, W> Drop for CopyPrioritized<R, W> {
+    fn drop(&mut self) {
+        // Don't let the writer get lost with us.
+        if let Some(mut waiting) = self.waiting.take() {
+            waiting.close();
+            if let Ok(Some(writer)) = waiting.try_recv() {
+                self.writer = Some(writer);
+            }
+        }
+        if let Some(writer) = self.writer.take() {
+            self.queue.release(writer);
+        }
+    }
+}
@@ -0,0 +1,256 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Application error code of a QUIC stream reset, for readers to wrap into
+/// the `io::Error` they fail with.
+///
+/// `io::Error::new(io::ErrorKind::ConnectionReset, QuicResetCode(code))` is
+/// turned into [`CopyQuicError::Reset`] by [`copy_quic`].
+///
+/// [`CopyQuicError::Reset`]: enum.CopyQuicError.html#variant.Reset
+/// [`copy_quic`]: fn.copy_quic.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuicResetCode(pub u64);
+
+impl fmt::Display for QuicResetCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "stream reset with code {}", self.0)
+    }
+}
+
+impl Error for QuicResetCode {}
+
+/// Error of [`copy_quic`].
+///
+/// [`copy_quic`]: fn.copy_quic.html
+#[derive(Debug)]
+pub enum CopyQuicError {
+    /// The peer reset the stream being read.
+    Reset {
+        /// Application error code of the reset.
+        error_code: u64,
+        /// Number of bytes written before the reset.
+        bytes_transferred: u64,
+    },
+    /// Any other I/O error.
+    Io(io::Error),
+}
+
+impl CopyQuicError {
+    /// Converts to an `io::Error`, a reset becoming `ConnectionReset`.
+    pub fn into_io_error(self) -> io::Error {
+        match self {
+            CopyQuicError::Io(e) => e,
+            e @ CopyQuicError::Reset { .. } => io::Error::new(io::ErrorKind::ConnectionReset, e),
+        }
+    }
+}
+
+impl fmt::Display for CopyQuicError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CopyQuicError::Reset { error_code, bytes_transferred } => {
+                write!(f, "stream reset with code {} after {} bytes",
+                       error_code, bytes_transferred)
+            }
+            CopyQuicError::Io(ref e) => e.fmt(f),
+        }
+    }
+}
+
+impl Error for CopyQuicError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match *self {
+            CopyQuicError::Reset { .. } => None,
+            CopyQuicError::Io(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for CopyQuicError {
+    fn from(e: io::Error) -> CopyQuicError {
+        CopyQuicError::Io(e)
+    }
+}
+
+impl From<CopyQuicError> for io::Error {
+    fn from(e: CopyQuicError) -> io::Error {
+        e.into_io_error()
+    }
+}
+
+/// A future which copies data like [`Copy`] from a QUIC stream, reporting
+/// stream resets separately.
+///
+/// Created by the [`copy_quic`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_quic`]: fn.copy_quic.html
+#[derive(Debug)]
+pub struct CopyQuic<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for reading a QUIC stream which may be
+/// reset by the peer mid-transfer.
+///
+/// An error from `reader` wrapping a [`QuicResetCode`], or any other
+/// `ConnectionReset` error, fails the copy with [`CopyQuicError::Reset`],
+/// carrying the application error code (0 if it's absent) and the number of
+/// bytes written so far. Other errors are [`CopyQuicError::Io`].
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`QuicResetCode`]: struct.QuicResetCode.html
+/// [`CopyQuicError::Reset`]: enum.CopyQuicError.html#variant.Reset
+/// [`CopyQuicError::Io`]: enum.CopyQuicError.html#variant.Io
+pub fn copy_quic<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyQuic<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyQuic {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyQuic<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = CopyQuicError;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), CopyQuicError> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = match reader.read(buf) {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => {
+                        let code = e.get_ref().and_then(|i| i.downcast_ref::<QuicResetCode>());
+                        if code.is_none() && e.kind() != io::ErrorKind::ConnectionReset {
+                            return Err(e.into());
+                        }
+                        return Err(CopyQuicError::Reset {
+                            error_code: code.map_or(0, |c| c.0),
+                            bytes_transferred: self.amt,
+                        });
+                    }
+                };
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer").into());
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor, Read};
+
+    use futures::Future;
+
+    use tokio_io::AsyncRead;
+
+    use super::*;
+
+    /// Hands out `data`, then fails with `error`.
+    struct ResetAfter {
+        data: Cursor<Vec<u8>>,
+        error: Option<io::Error>,
+    }
+
+    impl Read for ResetAfter {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.data.read(buf)? {
+                0 => Err(self.error.take().unwrap()),
+                n => Ok(n),
+            }
+        }
+    }
+
+    impl AsyncRead for ResetAfter {}
+
+    fn copy_until(error: io::Error) -> CopyQuicError {
+        let reader = ResetAfter {
+            data: Cursor::new(vec![1; 10]),
+            error: Some(error),
+        };
+        copy_quic(reader, Cursor::new(Vec::new()), vec![0; 4].into_boxed_slice())
+            .wait().err().expect("copy did not fail")
+    }
+
+    #[test]
+    fn reset_code() {
+        let e = io::Error::new(io::ErrorKind::ConnectionReset, QuicResetCode(0x1_0000_0042));
+        match copy_until(e) {
+            CopyQuicError::Reset { error_code, bytes_transferred } => {
+                assert_eq!(error_code, 0x1_0000_0042);
+                assert_eq!(bytes_transferred, 10);
+            }
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+
+    #[test]
+    fn other_errors() {
+        match copy_until(io::Error::new(io::ErrorKind::ConnectionReset, "reset")) {
+            CopyQuicError::Reset { error_code: 0, .. } => {}
+            e => panic!("unexpected error: {}", e),
+        }
+        match copy_until(io::ErrorKind::TimedOut.into()) {
+            CopyQuicError::Io(ref e) if e.kind() == io::ErrorKind::TimedOut => {}
+            e => panic!("unexpected error: {}", e),
+        }
+    }
+}
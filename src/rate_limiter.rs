@@ -0,0 +1,182 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Pluggable throttling algorithm for [`copy_with_rate_limiter`].
+///
+/// [`copy_with_rate_limiter`]: fn.copy_with_rate_limiter.html
+pub trait RateLimiter: Send {
+    /// Asks for permission to read `requested` bytes.
+    ///
+    /// Returns the number of bytes which may be read right now, which may be
+    /// less than `requested`. When nothing can be read yet, returns `NotReady`
+    /// and arranges for the current task to be notified later, like any other
+    /// `poll`-style function. `Ready(0)` is not allowed.
+    fn poll_allowed(&mut self, requested: usize) -> Async<usize>;
+}
+
+/// A future which copies data like [`Copy`], asking a [`RateLimiter`] before
+/// each read.
+///
+/// Created by the [`copy_with_rate_limiter`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`RateLimiter`]: trait.RateLimiter.html
+/// [`copy_with_rate_limiter`]: fn.copy_with_rate_limiter.html
+pub struct CopyRateLimiter<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    granted: usize,
+    buffer: Option<Box<[u8]>>,
+    limiter: Option<Box<dyn RateLimiter>>,
+}
+
+/// Variant of [`copy_with_buffer`] which reads only as much as `limiter`
+/// allows, so any throttling algorithm can be plugged in.
+///
+/// Bytes granted by the limiter but not read yet (e.g. on a short read) are
+/// used up before the limiter is asked again. A limiter returning `Ready(0)`
+/// instead of `NotReady` fails the copy with `InvalidData`, as nothing would
+/// wake it up again. The limiter is returned back on success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_rate_limiter<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                    limiter: Box<dyn RateLimiter>) -> CopyRateLimiter<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyRateLimiter {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        granted: 0,
+        buffer: Some(buffer),
+        limiter: Some(limiter),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug> fmt::Debug for CopyRateLimiter<R, W> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyRateLimiter")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("granted", &self.granted)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W> Future for CopyRateLimiter<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>, Box<dyn RateLimiter>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, Box<dyn RateLimiter>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, but only as much as the limiter lets us.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                if self.granted == 0 {
+                    let limiter = self.limiter.as_mut().unwrap();
+                    self.granted = match limiter.poll_allowed(buf.len()) {
+                        Async::Ready(n) => n,
+                        Async::NotReady => return Ok(Async::NotReady),
+                    };
+                    if self.granted == 0 {
+                        return Err(io::Error::new(io::ErrorKind::InvalidData,
+                                                  "rate limiter allowed zero bytes"));
+                    }
+                }
+                let reader = self.reader.as_mut().unwrap();
+                let len = cmp::min(buf.len(), self.granted);
+                let n = try_nb!(reader.read(&mut buf[..len]));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.granted -= n;
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let limiter = self.limiter.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, limiter).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    /// Allows the given amounts one after the other.
+    struct Steps(Vec<usize>);
+
+    impl RateLimiter for Steps {
+        fn poll_allowed(&mut self, requested: usize) -> Async<usize> {
+            Async::Ready(cmp::min(self.0.remove(0), requested))
+        }
+    }
+
+    #[test]
+    fn reads_what_is_allowed() {
+        let (amt, _, writer, _, _) =
+            copy_with_rate_limiter(Cursor::new(vec![1; 10]), Cursor::new(Vec::new()),
+                                   vec![0; 8].into_boxed_slice(), Box::new(Steps(vec![3, 8, 8])))
+                .wait()
+                .unwrap();
+        assert_eq!(amt, 10);
+        assert_eq!(writer.into_inner(), [1; 10]);
+    }
+
+    #[test]
+    fn zero_allowed_is_an_error() {
+        let r = copy_with_rate_limiter(Cursor::new(vec![1; 10]), Cursor::new(Vec::new()),
+                                       vec![0; 8].into_boxed_slice(),
+                                       Box::new(Steps(vec![3, 0])))
+            .wait();
+        assert_eq!(r.err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+}
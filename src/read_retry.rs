@@ -0,0 +1,114 @@
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], retrying reads which failed with
+/// a transient error.
+///
+/// Created by the [`copy_with_read_retry`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_read_retry`]: fn.copy_with_read_retry.html
+#[derive(Debug)]
+pub struct CopyReadRetry<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    max_retries: u32,
+    retries_left: u32,
+    retryable: fn(io::ErrorKind) -> bool,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which retries a failed read up to
+/// `max_retries` times if `retryable` returns true for the error kind,
+/// e.g. `ConnectionReset` or `BrokenPipe` on a flaky connection.
+///
+/// The retry counter is reset after each successful read. Other errors, or
+/// retryable ones after the retries are used up, fail the copy.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_read_retry<R, W>(reader: R, writer: W, buffer: Box<[u8]>, max_retries: u32,
+                                  retryable: fn(io::ErrorKind) -> bool) -> CopyReadRetry<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyReadRetry {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        max_retries,
+        retries_left: max_retries,
+        retryable,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyReadRetry<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = match reader.read(buf) {
+                    Ok(n) => n,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady)
+                    }
+                    Err(ref e) if (self.retryable)(e.kind()) && self.retries_left > 0 => {
+                        self.retries_left -= 1;
+                        continue;
+                    }
+                    Err(e) => return Err(e),
+                };
+                self.retries_left = self.max_retries;
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use std::io;
+#[cfg(unix)]
+use std::os::unix::io::AsRawFd;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and shuts down the read half of
+/// the reader's socket as soon as EOF is seen.
+///
+/// Created by the [`copy_with_read_shutdown`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_read_shutdown`]: fn.copy_with_read_shutdown.html
+#[derive(Debug)]
+pub struct CopyReadShutdown<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    read_shutdown_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which calls `shutdown(fd, SHUT_RD)` on the
+/// reader once it hits EOF, before the remaining data is written out.
+/// This way the peer gets a RST if it sends unexpected data afterwards.
+///
+/// The shutdown happens exactly once. `ENOTCONN` is ignored, other errors fail
+/// the copy. On non-Unix platforms the shutdown is a no-op.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+#[cfg(unix)]
+pub fn copy_with_read_shutdown<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> CopyReadShutdown<R, W>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite,
+{
+    new(reader, writer, buffer)
+}
+
+/// Variant of [`copy_with_buffer`] which calls `shutdown(fd, SHUT_RD)` on the
+/// reader once it hits EOF, before the remaining data is written out.
+/// This way the peer gets a RST if it sends unexpected data afterwards.
+///
+/// The shutdown happens exactly once. `ENOTCONN` is ignored, other errors fail
+/// the copy. On non-Unix platforms the shutdown is a no-op.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+#[cfg(not(unix))]
+pub fn copy_with_read_shutdown<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> CopyReadShutdown<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    new(reader, writer, buffer)
+}
+
+fn new<R, W>(reader: R, writer: W, buffer: Box<[u8]>) -> CopyReadShutdown<R, W> {
+    CopyReadShutdown {
+        reader: Some(reader),
+        read_done: false,
+        read_shutdown_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+#[cfg(unix)]
+fn shutdown_read<R: AsRawFd>(reader: &R) -> io::Result<()> {
+    let ret = unsafe { ::libc::shutdown(reader.as_raw_fd(), ::libc::SHUT_RD) };
+    if ret == -1 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(::libc::ENOTCONN) {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn shutdown_read<R>(_reader: &R) -> io::Result<()> {
+    Ok(())
+}
+
+type Finished<R, W> = (u64, R, W, Box<[u8]>);
+
+impl<R, W> CopyReadShutdown<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    fn poll_copy<F>(&mut self, shutdown: F) -> Poll<Finished<R, W>, io::Error>
+        where F: Fn(&R) -> io::Result<()>,
+    {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // Nothing more is expected from the reader, so close its read
+            // half before draining the rest.
+            if self.read_done && !self.read_shutdown_done {
+                self.read_shutdown_done = true;
+                shutdown(self.reader.as_ref().unwrap())?;
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+impl<R, W> Future for CopyReadShutdown<R, W>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        self.poll_copy(shutdown_read)
+    }
+}
+
+#[cfg(not(unix))]
+impl<R, W> Future for CopyReadShutdown<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        self.poll_copy(shutdown_read)
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+use std::io::{self, Write};
+use std::time::Instant;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and prints a summary line when done.
+///
+/// Created by the [`copy_with_report`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_report`]: fn.copy_with_report.html
+#[derive(Debug)]
+pub struct CopyReport<R, W, Out> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    reads: u64,
+    writes: u64,
+    started: Option<Instant>,
+    out: Option<Out>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for CLI tools and scripts, which on success
+/// writes a line like
+/// `Copied 1048576 bytes in 0.42s (2.4 MB/s, 16 reads, 8 writes)` to `out`.
+///
+/// Time is measured from the first poll. `out` is written synchronously, so it
+/// should be something quick like `stderr`. Failing to write the report fails
+/// the copy.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_report<R, W, Out>(reader: R, writer: W, buffer: Box<[u8]>, out: Out)
+    -> CopyReport<R, W, Out>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          Out: Write,
+{
+    CopyReport {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        reads: 0,
+        writes: 0,
+        started: None,
+        out: Some(out),
+        buffer: Some(buffer),
+    }
+}
+
+struct Rate(f64);
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let units = ["B/s", "kB/s", "MB/s", "GB/s", "TB/s"];
+        let mut rate = self.0;
+        let mut unit = 0;
+        while rate >= 1000.0 && unit < units.len() - 1 {
+            rate /= 1000.0;
+            unit += 1;
+        }
+        write!(f, "{:.1} {}", rate, units[unit])
+    }
+}
+
+impl<R, W, Out> Future for CopyReport<R, W, Out>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          Out: Write,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.reads += 1;
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.writes += 1;
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data, report and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let elapsed = self.started.unwrap().elapsed().as_secs_f64();
+                let rate = if elapsed > 0.0 { self.amt as f64 / elapsed } else { 0.0 };
+                let mut out = self.out.take().unwrap();
+                writeln!(out, "Copied {} bytes in {:.2}s ({}, {} reads, {} writes)",
+                         self.amt, elapsed, Rate(rate), self.reads, self.writes)?;
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use futures::{Async, Poll};
+use futures::task::AtomicTask;
+
+use ringbuf::traits::{Consumer, Producer};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Wakes up the task blocked on one side of a ring buffer.
+#[derive(Debug, Clone)]
+struct RingBufNotify {
+    inner: Arc<AtomicTask>,
+}
+
+impl RingBufNotify {
+    fn new() -> RingBufNotify {
+        RingBufNotify {
+            inner: Arc::new(AtomicTask::new()),
+        }
+    }
+
+    fn register(&self) {
+        self.inner.register();
+    }
+
+    fn notify(&self) {
+        self.inner.notify();
+    }
+}
+
+/// Wraps the two halves of a `ringbuf` ring buffer into an `AsyncWrite` and
+/// an `AsyncRead`, for use with [`copy_with_buffer`].
+///
+/// The halves wake each other up: pushing data wakes up a blocked reader,
+/// popping data wakes up a blocked writer, and dropping either half wakes up
+/// the other one to see it gone.
+///
+/// Available with the `ringbuf` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn ring_buf_pair<P, C>(producer: P, consumer: C) -> (RingBufWriter<P>, RingBufReader<C>)
+    where P: Producer<Item = u8>,
+          C: Consumer<Item = u8>,
+{
+    let write_notify = RingBufNotify::new();
+    let read_notify = RingBufNotify::new();
+    let writer = RingBufWriter {
+        producer: Some(producer),
+        notify: write_notify.clone(),
+        peer: read_notify.clone(),
+    };
+    let reader = RingBufReader {
+        consumer: Some(consumer),
+        notify: read_notify,
+        peer: write_notify,
+    };
+    (writer, reader)
+}
+
+/// `AsyncRead` over the consuming half of a `ringbuf` ring buffer.
+///
+/// Reads pop as many bytes as are available. When the ring buffer is empty,
+/// reads return `WouldBlock` until the [`RingBufWriter`] pushes data. Once the
+/// ring buffer is empty and its producer is dropped, reads return EOF.
+///
+/// Created by the [`ring_buf_pair`] function.
+///
+/// [`RingBufWriter`]: struct.RingBufWriter.html
+/// [`ring_buf_pair`]: fn.ring_buf_pair.html
+pub struct RingBufReader<T: Consumer<Item = u8>> {
+    consumer: Option<T>,
+    notify: RingBufNotify,
+    peer: RingBufNotify,
+}
+
+impl<T: Consumer<Item = u8>> RingBufReader<T> {
+    /// Returns the inner consumer.
+    pub fn into_inner(mut self) -> T {
+        self.consumer.take().unwrap()
+    }
+}
+
+impl<T: Consumer<Item = u8>> Drop for RingBufReader<T> {
+    fn drop(&mut self) {
+        // Release the consumer first, so that the woken up writer sees it
+        // gone.
+        drop(self.consumer.take());
+        self.peer.notify();
+    }
+}
+
+impl<T: Consumer<Item = u8>> fmt::Debug for RingBufReader<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RingBufReader")
+            .field("notify", &self.notify)
+            .field("peer", &self.peer)
+            .finish()
+    }
+}
+
+impl<T: Consumer<Item = u8>> Read for RingBufReader<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let consumer = self.consumer.as_mut().unwrap();
+        let mut n = consumer.pop_slice(buf);
+        if n == 0 {
+            // Register before checking again, so that a push between the two
+            // checks is not missed.
+            self.notify.register();
+            n = consumer.pop_slice(buf);
+            if n == 0 && consumer.write_is_held() {
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+        }
+        if n > 0 {
+            self.peer.notify();
+        }
+        Ok(n)
+    }
+}
+
+impl<T: Consumer<Item = u8>> AsyncRead for RingBufReader<T> {}
+
+/// `AsyncWrite` over the producing half of a `ringbuf` ring buffer.
+///
+/// Writes push as many bytes as fit. When the ring buffer is full, writes
+/// return `WouldBlock` until the [`RingBufReader`] pops data. Once the
+/// consumer is dropped, writes fail with `BrokenPipe`.
+///
+/// Created by the [`ring_buf_pair`] function.
+///
+/// [`RingBufReader`]: struct.RingBufReader.html
+/// [`ring_buf_pair`]: fn.ring_buf_pair.html
+pub struct RingBufWriter<T: Producer<Item = u8>> {
+    producer: Option<T>,
+    notify: RingBufNotify,
+    peer: RingBufNotify,
+}
+
+impl<T: Producer<Item = u8>> RingBufWriter<T> {
+    /// Returns the inner producer.
+    pub fn into_inner(mut self) -> T {
+        self.producer.take().unwrap()
+    }
+}
+
+impl<T: Producer<Item = u8>> Drop for RingBufWriter<T> {
+    fn drop(&mut self) {
+        // Release the producer first, so that the woken up reader sees EOF.
+        drop(self.producer.take());
+        self.peer.notify();
+    }
+}
+
+impl<T: Producer<Item = u8>> fmt::Debug for RingBufWriter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RingBufWriter")
+            .field("notify", &self.notify)
+            .field("peer", &self.peer)
+            .finish()
+    }
+}
+
+fn consumer_gone() -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, "ring buffer consumer is gone")
+}
+
+impl<T: Producer<Item = u8>> Write for RingBufWriter<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+        let producer = self.producer.as_mut().unwrap();
+        if !producer.read_is_held() {
+            return Err(consumer_gone());
+        }
+        let mut n = producer.push_slice(buf);
+        if n == 0 {
+            self.notify.register();
+            n = producer.push_slice(buf);
+            if n == 0 {
+                if !producer.read_is_held() {
+                    return Err(consumer_gone());
+                }
+                return Err(io::ErrorKind::WouldBlock.into());
+            }
+        }
+        self.peer.notify();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<T: Producer<Item = u8>> AsyncWrite for RingBufWriter<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        Ok(Async::Ready(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use futures::Future;
+
+    use ringbuf::HeapRb;
+    use ringbuf::traits::Split;
+
+    use tokio_io::io::write_all;
+
+    use copy_with_buffer;
+
+    use super::*;
+
+    #[test]
+    fn halves_wake_each_other() {
+        let (producer, consumer) = HeapRb::<u8>::new(16).split();
+        let (writer, reader) = ring_buf_pair(producer, consumer);
+        let data: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+
+        // Both halves run in one task, so neither makes progress unless the
+        // other one wakes it up.
+        let write = write_all(writer, data.clone()).map(drop);
+        let copy = copy_with_buffer(reader, Cursor::new(Vec::new()),
+                                    vec![0; 64].into_boxed_slice());
+        let ((), (n, _, out, _)) = write.join(copy).wait().unwrap();
+        assert_eq!(n, data.len() as u64);
+        assert_eq!(out.into_inner(), data);
+    }
+}
@@ -0,0 +1,121 @@
+use std::cmp;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+const SMALL_READ_STREAK: u8 = 8;
+
+/// A future which copies data like [`Copy`], shrinking its buffer when reads
+/// stay small.
+///
+/// Created by the [`copy_shrink`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_shrink`]: fn.copy_shrink.html
+#[derive(Debug)]
+pub struct CopyShrink<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    min: usize,
+    small_read_streak: u8,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for low-throughput connections, which
+/// allocates an `initial` sized buffer and halves it, down to `min` bytes,
+/// after 8 reads in a row each filling less than a quarter of it.
+///
+/// A read filling a quarter or more resets the streak. The final buffer is
+/// returned on success.
+///
+/// # Panics
+///
+/// Panics if `min` is zero or larger than `initial`.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_shrink<R, W>(reader: R, writer: W, initial: usize, min: usize) -> CopyShrink<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(min > 0, "minimum buffer size must not be zero");
+    assert!(min <= initial, "minimum buffer size is larger than the initial one");
+    CopyShrink {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        min,
+        small_read_streak: 0,
+        buffer: Some(vec![0; initial].into_boxed_slice()),
+    }
+}
+
+impl<R, W> Future for CopyShrink<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, shrink it if reads have been small for
+            // a while, then read some data to continue.
+            if self.pos == self.cap && !self.read_done {
+                let len = self.buffer.as_ref().unwrap().len();
+                if self.small_read_streak >= SMALL_READ_STREAK && len > self.min {
+                    let new_len = cmp::max(len / 2, self.min);
+                    self.buffer = Some(vec![0; new_len].into_boxed_slice());
+                    self.small_read_streak = 0;
+                }
+
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    if n < buf.len() / 4 {
+                        self.small_read_streak = self.small_read_streak.saturating_add(1);
+                    } else {
+                        self.small_read_streak = 0;
+                    }
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
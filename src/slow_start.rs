@@ -0,0 +1,137 @@
+use std::cmp;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], adapting the read size to how
+/// much data the reader has.
+///
+/// Created by the [`copy_slow_start`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_slow_start`]: fn.copy_slow_start.html
+#[derive(Debug)]
+pub struct CopySlowStart<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    initial_read: usize,
+    current_read_size: usize,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which, like TCP slow start, begins with
+/// reads of `initial_read` bytes and doubles the read size after each read
+/// which fills it completely, up to the buffer length. A read which returns
+/// less halves it again, down to `initial_read`.
+///
+/// This avoids large reads from pipes which only ever have a few bytes.
+/// `initial_read` is capped to the buffer length.
+///
+/// # Panics
+///
+/// Panics if `initial_read` is zero or the buffer is empty.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_slow_start<R, W>(reader: R, writer: W, buffer: Box<[u8]>, initial_read: usize)
+    -> CopySlowStart<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    let initial_read = cmp::min(initial_read, buffer.len());
+    assert!(initial_read > 0, "initial read size must not be zero");
+    CopySlowStart {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        initial_read,
+        current_read_size: initial_read,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> CopySlowStart<R, W> {
+    /// Size of the next read.
+    pub fn current_read_size(&self) -> usize {
+        self.current_read_size
+    }
+}
+
+impl<R, W> Future for CopySlowStart<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, and adjust the next read size.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(&mut buf[..self.current_read_size]));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    if n == self.current_read_size {
+                        self.current_read_size = cmp::min(self.current_read_size.saturating_mul(2),
+                                                          buf.len());
+                    } else {
+                        self.current_read_size = cmp::max(self.current_read_size / 2,
+                                                          self.initial_read);
+                    }
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    #[should_panic(expected = "initial read size must not be zero")]
+    fn empty_buffer_rejected() {
+        copy_slow_start(Cursor::new(vec![1; 10]), Cursor::new(Vec::new()),
+                        Vec::new().into_boxed_slice(), 4);
+    }
+}
@@ -0,0 +1,140 @@
+use std::cmp;
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+const SNIFF_LEN: usize = 512;
+
+const MAGIC: &[(&[u8], &str)] = &[
+    (b"\xFF\xD8\xFF", "image/jpeg"),
+    (b"\x89PNG\r\n\x1A\n", "image/png"),
+    (b"%PDF-", "application/pdf"),
+    (b"PK\x03\x04", "application/zip"),
+    (b"PK\x05\x06", "application/zip"),
+    (b"MZ", "application/vnd.microsoft.portable-executable"),
+];
+
+fn sniff_mime(data: &[u8]) -> Option<&'static str> {
+    MAGIC.iter().find(|&&(magic, _)| data.starts_with(magic)).map(|&(_, mime)| mime)
+}
+
+/// A future which copies data like [`Copy`], but lets a callback inspect the
+/// type of the content first.
+///
+/// Created by the [`copy_with_sniff`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_sniff`]: fn.copy_with_sniff.html
+pub struct CopySniff<R, W, F> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    on_mime: Option<F>,
+}
+
+/// Variant of [`copy_with_buffer`] for upload servers which need to check
+/// file types.
+///
+/// Before anything is written, `on_mime` is called once with the MIME type
+/// detected from the magic bytes of the first chunk, if any, and the first
+/// chunk itself (at most 512 bytes of it). JPEG, PNG, PDF, ZIP and Windows
+/// executables are recognized. If the callback returns an error, the copy
+/// fails with it; otherwise the first chunk is written out as usual.
+///
+/// For empty input, the callback is not called.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_sniff<R, W, F>(reader: R, writer: W, buffer: Box<[u8]>, on_mime: F)
+    -> CopySniff<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnOnce(Option<&'static str>, &[u8]) -> io::Result<()>,
+{
+    CopySniff {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        on_mime: Some(on_mime),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F> fmt::Debug for CopySniff<R, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopySniff")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .field("sniffed", &self.on_mime.is_none())
+            .finish()
+    }
+}
+
+impl<R, W, F> Future for CopySniff<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnOnce(Option<&'static str>, &[u8]) -> io::Result<()>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue. The first chunk is sniffed.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    if let Some(on_mime) = self.on_mime.take() {
+                        let head = &buf[..cmp::min(n, SNIFF_LEN)];
+                        on_mime(sniff_mime(head), head)?;
+                    }
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
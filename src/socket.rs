@@ -0,0 +1,194 @@
+use std::io;
+use std::mem;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::time::Duration;
+
+use libc;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use {copy_with_buffer, Copy};
+
+/// Sets an integer socket option, treating `ENOTSOCK` as success so that
+/// non-socket file descriptors are silently left alone.
+pub(crate) fn set_int_option(fd: RawFd, level: libc::c_int, name: libc::c_int, value: libc::c_int)
+    -> io::Result<()>
+{
+    let ret = unsafe {
+        libc::setsockopt(fd, level, name,
+                         &value as *const libc::c_int as *const libc::c_void,
+                         mem::size_of::<libc::c_int>() as libc::socklen_t)
+    };
+    if ret == -1 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ENOTSOCK) {
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Gets an integer socket option, or `None` if `fd` is not a socket.
+pub(crate) fn get_int_option(fd: RawFd, level: libc::c_int, name: libc::c_int)
+    -> io::Result<Option<libc::c_int>>
+{
+    let mut value: libc::c_int = 0;
+    let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+    let ret = unsafe {
+        libc::getsockopt(fd, level, name,
+                         &mut value as *mut libc::c_int as *mut libc::c_void, &mut len)
+    };
+    if ret == -1 {
+        let err = io::Error::last_os_error();
+        if err.raw_os_error() == Some(libc::ENOTSOCK) {
+            return Ok(None);
+        }
+        return Err(err);
+    }
+    Ok(Some(value))
+}
+
+/// Creates a [`copy_with_buffer`] future after sizing the kernel socket buffers
+/// to match the copy buffer: `SO_RCVBUF` of `reader` and `SO_SNDBUF` of `writer`
+/// are set to twice the buffer length.
+///
+/// This is a best-effort optimization for high-bandwidth networks. File
+/// descriptors which are not sockets are left alone; other `setsockopt`
+/// failures are returned as errors.
+///
+/// Available on Unix.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_socket_tuning<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> io::Result<Copy<R, W>>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite + AsRawFd,
+{
+    let size = buffer.len().saturating_mul(2);
+    let size = if size > libc::c_int::MAX as usize { libc::c_int::MAX } else { size as libc::c_int };
+    set_int_option(reader.as_raw_fd(), libc::SOL_SOCKET, libc::SO_RCVBUF, size)?;
+    set_int_option(writer.as_raw_fd(), libc::SOL_SOCKET, libc::SO_SNDBUF, size)?;
+    Ok(copy_with_buffer(reader, writer, buffer))
+}
+
+/// Creates a [`copy_with_buffer`] future after enabling TCP keepalive on both
+/// `reader` and `writer`, so that connections through NATs are not silently
+/// dropped while the copy stalls.
+///
+/// Sets `SO_KEEPALIVE` and, on Linux, Android and FreeBSD, both
+/// `TCP_KEEPIDLE` and `TCP_KEEPINTVL` to `keepalive_interval` in whole
+/// seconds (at least one). File descriptors which are not sockets are left
+/// alone, as are the TCP options of sockets which are not TCP; other
+/// `setsockopt` failures are returned as errors.
+///
+/// Available on Unix.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_keepalive<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                 keepalive_interval: Duration)
+    -> io::Result<Copy<R, W>>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite + AsRawFd,
+{
+    for &fd in &[reader.as_raw_fd(), writer.as_raw_fd()] {
+        set_int_option(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+        set_keepalive_interval(fd, keepalive_interval)?;
+    }
+    Ok(copy_with_buffer(reader, writer, buffer))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn set_keepalive_interval(fd: RawFd, interval: Duration) -> io::Result<()> {
+    let secs = interval.as_secs();
+    let secs = if secs > libc::c_int::MAX as u64 { libc::c_int::MAX } else { secs as libc::c_int };
+    for &name in &[libc::TCP_KEEPIDLE, libc::TCP_KEEPINTVL] {
+        match set_int_option(fd, libc::IPPROTO_TCP, name, secs.max(1)) {
+            // Not a TCP socket, e.g. a Unix domain one.
+            Err(ref e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => {}
+            r => r?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn set_keepalive_interval(_fd: RawFd, _interval: Duration) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::net::{TcpListener, TcpStream};
+    use std::os::unix::net::UnixStream;
+
+    use futures::Poll;
+
+    use super::*;
+
+    /// Lets blocking std sockets stand in for async ones.
+    #[derive(Debug)]
+    struct Sock<T>(T);
+
+    impl<T: Read> Read for Sock<T> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<T: Read> AsyncRead for Sock<T> {}
+
+    impl<T: Write> Write for Sock<T> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl<T: Write> AsyncWrite for Sock<T> {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    impl<T: AsRawFd> AsRawFd for Sock<T> {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    #[test]
+    fn keepalive_on_tcp() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        let copy = copy_with_keepalive(Sock(client), Sock(server), vec![0; 16].into_boxed_slice(),
+                                       Duration::from_secs(42))
+            .unwrap();
+        let fd = copy.reader.as_ref().unwrap().as_raw_fd();
+        let get = |level, name| {
+            let mut value: libc::c_int = 0;
+            let mut len = mem::size_of::<libc::c_int>() as libc::socklen_t;
+            let ret = unsafe {
+                libc::getsockopt(fd, level, name,
+                                 &mut value as *mut libc::c_int as *mut libc::c_void, &mut len)
+            };
+            assert_eq!(ret, 0);
+            value
+        };
+        assert_ne!(get(libc::SOL_SOCKET, libc::SO_KEEPALIVE), 0);
+        assert_eq!(get(libc::IPPROTO_TCP, libc::TCP_KEEPIDLE), 42);
+        assert_eq!(get(libc::IPPROTO_TCP, libc::TCP_KEEPINTVL), 42);
+    }
+
+    #[test]
+    fn keepalive_on_unix_socket() {
+        let (a, b) = UnixStream::pair().unwrap();
+        copy_with_keepalive(Sock(a), Sock(b), vec![0; 16].into_boxed_slice(),
+                            Duration::from_secs(42))
+            .unwrap();
+    }
+}
@@ -0,0 +1,162 @@
+use std::io;
+use std::mem;
+use std::os::unix::io::AsRawFd;
+
+use futures::{Future, Poll};
+
+use libc;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data into a file, punching holes instead of writing
+/// chunks which are all zeroes.
+///
+/// Created by the [`copy_sparse`] function.
+///
+/// [`copy_sparse`]: fn.copy_sparse.html
+#[derive(Debug)]
+pub struct CopySparse<R, F> {
+    reader: Option<R>,
+    read_done: bool,
+    file: Option<F>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    punched: u64,
+    current_offset: Option<u64>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Creates a future which copies all bytes from `reader` into `file` like
+/// [`copy_with_buffer`], but keeps long runs of zeroes sparse.
+///
+/// Each chunk read entirely of zeroes is not written; instead the region is
+/// deallocated with `fallocate(FALLOC_FL_PUNCH_HOLE | FALLOC_FL_KEEP_SIZE)`
+/// and the file position is moved past it. If the filesystem does not support
+/// punching holes, the zeroes are written normally. The file is extended to
+/// the final size if it ends with a hole.
+///
+/// `file` must write straight to its file descriptor without buffering.
+/// On success the number of bytes copied (including holes) is followed by
+/// the number of bytes punched.
+///
+/// Available on Linux with the `sparse` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_sparse<R, F>(reader: R, file: F, buffer: Box<[u8]>) -> CopySparse<R, F>
+    where R: AsyncRead,
+          F: AsyncWrite + AsRawFd,
+{
+    CopySparse {
+        reader: Some(reader),
+        read_done: false,
+        file: Some(file),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        punched: 0,
+        current_offset: None,
+        buffer: Some(buffer),
+    }
+}
+
+fn is_all_zeroes(buf: &[u8]) -> bool {
+    buf.chunks(64).all(|c| c.iter().fold(0, |acc, &b| acc | b) == 0)
+}
+
+fn cvt(ret: i64) -> io::Result<i64> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret)
+    }
+}
+
+fn seek_forward(fd: i32, len: u64) -> io::Result<u64> {
+    let ret = unsafe { libc::lseek(fd, len as libc::off_t, libc::SEEK_CUR) };
+    cvt(ret as i64).map(|off| off as u64)
+}
+
+/// Returns false if punching holes is not supported here.
+fn punch_hole(fd: i32, offset: u64, len: u64) -> io::Result<bool> {
+    let mode = libc::FALLOC_FL_PUNCH_HOLE | libc::FALLOC_FL_KEEP_SIZE;
+    let ret = unsafe {
+        libc::fallocate(fd, mode, offset as libc::off_t, len as libc::off_t)
+    };
+    match cvt(ret as i64) {
+        Ok(_) => Ok(true),
+        Err(ref e) if e.raw_os_error() == Some(libc::EOPNOTSUPP) => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+fn extend_to(fd: i32, len: u64) -> io::Result<()> {
+    let mut st: libc::stat = unsafe { mem::zeroed() };
+    cvt(unsafe { libc::fstat(fd, &mut st) } as i64)?;
+    if (st.st_size as u64) < len {
+        cvt(unsafe { libc::ftruncate(fd, len as libc::off_t) } as i64)?;
+    }
+    Ok(())
+}
+
+impl<R, F> Future for CopySparse<R, F>
+    where R: AsyncRead,
+          F: AsyncWrite + AsRawFd,
+{
+    type Item = (u64, u64, R, F, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, u64, R, F, Box<[u8]>), io::Error> {
+        let fd = self.file.as_ref().unwrap().as_raw_fd();
+        if self.current_offset.is_none() {
+            self.current_offset = Some(seek_forward(fd, 0)?);
+        }
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue. Zero chunks are turned into holes right away.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else if is_all_zeroes(&buf[..n])
+                    && punch_hole(fd, self.current_offset.unwrap(), n as u64)? {
+                    self.current_offset = Some(seek_forward(fd, n as u64)?);
+                    self.punched += n as u64;
+                    self.amt += n as u64;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let file = self.file.as_mut().unwrap();
+                let i = try_nb!(file.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                    self.current_offset = self.current_offset.map(|o| o + i as u64);
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, make sure a
+            // trailing hole is part of the file, flush out the data and finish
+            // the transfer.
+            if self.pos == self.cap && self.read_done {
+                extend_to(fd, self.current_offset.unwrap())?;
+                try_nb!(self.file.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let file = self.file.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, self.punched, reader, file, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use std::io::{self, PipeReader};
+use std::os::unix::io::AsRawFd;
+use std::ptr;
+
+use futures::{Async, Future, Poll};
+
+use libc;
+
+use fd_ready::FdReady;
+
+const CHUNK: usize = 65536;
+
+/// A future which copies data from a pipe into two destinations without
+/// passing it through userspace.
+///
+/// Created by the [`copy_tee_splice`] function.
+///
+/// [`copy_tee_splice`]: fn.copy_tee_splice.html
+#[derive(Debug)]
+pub struct CopyTeeSplice<W1, W2> {
+    pipe_reader: Option<PipeReader>,
+    w1: Option<W1>,
+    w2: Option<W2>,
+    pending: usize,
+    amt: u64,
+    in_ready: FdReady,
+    w1_ready: FdReady,
+    w2_ready: FdReady,
+}
+
+/// Creates a future which duplicates everything from `pipe_reader` into both
+/// `w1` and `w2`, using `tee(2)` to clone up to 64 KiB of the pipe contents
+/// into `w1` and then `splice(2)` to move the same bytes into `w2`.
+///
+/// `w1` must be a pipe; `w2` can be anything `splice` accepts, like a socket
+/// or a file. When no data can be moved, the future waits for the blocking
+/// descriptors on the default reactor, registering duplicates of them, so
+/// they may already be registered elsewhere. On success the number of bytes
+/// copied (to each destination) is returned.
+///
+/// Available on Linux.
+pub fn copy_tee_splice<W1, W2>(pipe_reader: PipeReader, w1: W1, w2: W2) -> CopyTeeSplice<W1, W2>
+    where W1: AsRawFd,
+          W2: AsRawFd,
+{
+    CopyTeeSplice {
+        pipe_reader: Some(pipe_reader),
+        w1: Some(w1),
+        w2: Some(w2),
+        pending: 0,
+        amt: 0,
+        in_ready: FdReady::default(),
+        w1_ready: FdReady::default(),
+        w2_ready: FdReady::default(),
+    }
+}
+
+fn cvt(ret: libc::ssize_t) -> io::Result<usize> {
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+impl<W1, W2> Future for CopyTeeSplice<W1, W2>
+    where W1: AsRawFd,
+          W2: AsRawFd,
+{
+    type Item = (u64, PipeReader, W1, W2);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, PipeReader, W1, W2), io::Error> {
+        let fd_in = self.pipe_reader.as_ref().unwrap().as_raw_fd();
+        loop {
+            // Clone the next piece of the pipe into the first destination.
+            // `tee` gives 0 once the pipe is empty and its writer is closed.
+            let res = if self.pending == 0 {
+                let fd_out = self.w1.as_ref().unwrap().as_raw_fd();
+                cvt(unsafe { libc::tee(fd_in, fd_out, CHUNK, libc::SPLICE_F_NONBLOCK) })
+                    .map(|n| {
+                        self.pending = n;
+                        n == 0
+                    })
+            } else {
+                // Now move the same bytes into the second one.
+                let fd_out = self.w2.as_ref().unwrap().as_raw_fd();
+                cvt(unsafe {
+                    libc::splice(fd_in, ptr::null_mut(), fd_out, ptr::null_mut(),
+                                 self.pending, libc::SPLICE_F_NONBLOCK)
+                }).and_then(|n| {
+                    if n == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "splice moved zero bytes"));
+                    }
+                    self.pending -= n;
+                    self.amt += n as u64;
+                    Ok(false)
+                })
+            };
+
+            match res {
+                Ok(false) => {}
+                Ok(true) => {
+                    // Close the duplicates, which would keep the pipes open.
+                    self.in_ready = FdReady::default();
+                    self.w1_ready = FdReady::default();
+                    self.w2_ready = FdReady::default();
+                    let pipe_reader = self.pipe_reader.take().unwrap();
+                    let w1 = self.w1.take().unwrap();
+                    let w2 = self.w2.take().unwrap();
+                    return Ok((self.amt, pipe_reader, w1, w2).into())
+                }
+                Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    // `tee` blocks on an empty source or a full first
+                    // destination, `splice` only on the second destination.
+                    if self.pending == 0 {
+                        self.in_ready.wait_readable(fd_in)?;
+                        self.w1_ready.wait_writable(self.w1.as_ref().unwrap().as_raw_fd())?;
+                    } else {
+                        self.w2_ready.wait_writable(self.w2.as_ref().unwrap().as_raw_fd())?;
+                    }
+                    return Ok(Async::NotReady);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Read, Write};
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::{future, Future};
+
+    use super::*;
+
+    #[test]
+    fn waits_for_a_stalled_pipe() {
+        let (pipe_reader, mut pipe_writer) = io::pipe().unwrap();
+        let (mut r1, w1) = io::pipe().unwrap();
+        let (mut r2, w2) = io::pipe().unwrap();
+        let feeder = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            pipe_writer.write_all(b"late data").unwrap();
+        });
+
+        let mut copy = copy_tee_splice(pipe_reader, w1, w2);
+        let mut polls = 0;
+        let (n, _, w1, w2) = future::poll_fn(|| {
+            polls += 1;
+            copy.poll()
+        }).wait().unwrap();
+        feeder.join().unwrap();
+        drop((w1, w2));
+
+        // A busy loop would poll thousands of times while the pipe is idle.
+        assert!(polls < 10, "polled {} times", polls);
+        assert_eq!(n, 9);
+        let mut out = Vec::new();
+        r1.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"late data");
+        out.clear();
+        r2.read_to_end(&mut out).unwrap();
+        assert_eq!(out, b"late data");
+    }
+}
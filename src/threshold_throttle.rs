@@ -0,0 +1,234 @@
+use std::cmp;
+use std::io;
+use std::time::{Duration, Instant};
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+use tokio_timer::Delay;
+
+/// Current mode of a [`copy_threshold_throttle`] future.
+///
+/// [`copy_threshold_throttle`]: fn.copy_threshold_throttle.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ThrottleMode {
+    /// Copying at full speed.
+    Burst,
+    /// Copying at the steady rate.
+    Throttled,
+}
+
+/// A future which copies data like [`Copy`] at full speed at first, then at
+/// a limited rate.
+///
+/// Created by the [`copy_threshold_throttle`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_threshold_throttle`]: fn.copy_threshold_throttle.html
+#[derive(Debug)]
+pub struct CopyThrottleThreshold<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    burst_limit: u64,
+    steady_bps: u64,
+    mode: ThrottleMode,
+    tokens: f64,
+    refilled: Instant,
+    sleep: Option<Delay>,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for bursty-then-sustained transfers: the
+/// first `burst_limit` bytes are copied at full speed, the rest at
+/// `steady_bps` bytes per second using a token bucket holding at most one
+/// second worth of tokens.
+///
+/// The switch happens before the first read after `burst_limit` bytes have
+/// been written, so the chunk crossing the limit is copied in full.
+/// Needs to be run within a `tokio-timer` timer context.
+///
+/// Available with the `tokio-timer` feature.
+///
+/// # Panics
+///
+/// Panics if `steady_bps` is zero.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_threshold_throttle<R, W>(reader: R, writer: W, buffer: Box<[u8]>,
+                                     burst_limit: u64, steady_bps: u64)
+    -> CopyThrottleThreshold<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(steady_bps > 0, "steady rate must not be zero");
+    CopyThrottleThreshold {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        burst_limit,
+        steady_bps,
+        mode: ThrottleMode::Burst,
+        tokens: 0.0,
+        refilled: Instant::now(),
+        sleep: None,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> CopyThrottleThreshold<R, W> {
+    /// Whether the copy is still bursting or already throttled.
+    pub fn mode(&self) -> ThrottleMode {
+        self.mode
+    }
+
+    /// Waits until `want` bytes may be read.
+    fn poll_tokens(&mut self, want: usize) -> Poll<(), io::Error> {
+        loop {
+            let now = Instant::now();
+            let elapsed = now.duration_since(self.refilled).as_secs_f64();
+            self.tokens = (self.tokens + elapsed * self.steady_bps as f64)
+                .min(self.steady_bps as f64);
+            self.refilled = now;
+            if self.tokens >= want as f64 {
+                return Ok(Async::Ready(()));
+            }
+
+            let wait = (want as f64 - self.tokens) / self.steady_bps as f64;
+            let deadline = now + Duration::from_secs_f64(wait);
+            match self.sleep {
+                Some(ref mut sleep) => sleep.reset(deadline),
+                None => self.sleep = Some(Delay::new(deadline)),
+            }
+            match self.sleep.as_mut().unwrap().poll() {
+                Ok(Async::Ready(())) => {}
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => return Err(io::Error::other(e)),
+            }
+        }
+    }
+}
+
+impl<R, W> Future for CopyThrottleThreshold<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, waiting for tokens once past the burst.
+            if self.pos == self.cap && !self.read_done {
+                if self.mode == ThrottleMode::Burst && self.amt >= self.burst_limit {
+                    self.mode = ThrottleMode::Throttled;
+                    self.refilled = Instant::now();
+                }
+                let mut want = self.buffer.as_ref().unwrap().len();
+                if self.mode == ThrottleMode::Throttled {
+                    want = cmp::min(want, cmp::min(self.steady_bps, usize::MAX as u64) as usize);
+                    try_ready!(self.poll_tokens(want));
+                }
+
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(&mut buf[..want]));
+                if self.mode == ThrottleMode::Throttled {
+                    self.tokens -= n as f64;
+                }
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{self, Cursor};
+
+    use futures::future;
+
+    use tokio::runtime::current_thread::Runtime;
+
+    use super::*;
+
+    /// Records when each write happens.
+    #[derive(Debug)]
+    struct Stamps(Vec<Instant>);
+
+    impl io::Write for Stamps {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.push(Instant::now());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Stamps {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(().into())
+        }
+    }
+
+    #[test]
+    fn throttled_after_burst() {
+        let start = Instant::now();
+        let mut copy = copy_threshold_throttle(Cursor::new(vec![1; 200]), Stamps(Vec::new()),
+                                               vec![0; 50].into_boxed_slice(), 100, 100);
+        assert_eq!(copy.mode(), ThrottleMode::Burst);
+        let mut mode = None;
+        let (amt, _, writer, _) = Runtime::new().unwrap().block_on(future::poll_fn(|| {
+            let r = copy.poll();
+            mode = Some(copy.mode());
+            r
+        })).unwrap();
+        assert_eq!(amt, 200);
+        assert_eq!(mode, Some(ThrottleMode::Throttled));
+
+        // The burst is written at once, then 50 bytes every half a second.
+        let at: Vec<Duration> = writer.0.iter().map(|t| t.duration_since(start)).collect();
+        assert_eq!(at.len(), 4);
+        assert!(at[1] < Duration::from_millis(200), "{:?}", at);
+        assert!(at[2] >= Duration::from_millis(450), "{:?}", at);
+        assert!(at[3] - at[2] >= Duration::from_millis(450), "{:?}", at);
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], transforming each chunk in the
+/// buffer before writing it.
+///
+/// Created by the [`copy_transform_in_place`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_transform_in_place`]: fn.copy_transform_in_place.html
+pub struct CopyInPlace<R, W, F> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    transform: Option<F>,
+}
+
+/// Variant of [`copy_with_buffer`] which applies `transform` to each chunk
+/// right in the buffer, e.g. an XOR mask or case folding, so no second buffer
+/// is needed.
+///
+/// `transform` gets the whole buffer and the number of bytes read into it, and
+/// returns how many bytes from the start of the buffer are to be written.
+/// Returning more than the buffer length panics. The number of written bytes
+/// is returned on success, together with `transform`.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_transform_in_place<R, W, F>(reader: R, writer: W, buffer: Box<[u8]>, transform: F)
+    -> CopyInPlace<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(&mut [u8], usize) -> usize,
+{
+    CopyInPlace {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        transform: Some(transform),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F> fmt::Debug for CopyInPlace<R, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyInPlace")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W, F> Future for CopyInPlace<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(&mut [u8], usize) -> usize,
+{
+    type Item = (u64, R, W, Box<[u8]>, F);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, F), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, and transform it.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    let out = (self.transform.as_mut().unwrap())(buf, n);
+                    assert!(out <= buf.len(), "transform produced more bytes than the buffer holds");
+                    self.pos = 0;
+                    self.cap = out;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                let transform = self.transform.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer, transform).into())
+            }
+        }
+    }
+}
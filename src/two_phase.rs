@@ -0,0 +1,157 @@
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], with a prepare step before and a
+/// commit step after each write.
+///
+/// Created by the [`copy_two_phase`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_two_phase`]: fn.copy_two_phase.html
+pub struct CopyTwoPhase<R, W, P, PFut, C, CFut> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    prepare: P,
+    prepare_fut: Option<PFut>,
+    prepared: bool,
+    commit: C,
+    commit_fut: Option<CFut>,
+    committing: usize,
+}
+
+/// Variant of [`copy_with_buffer`] modelling write-ahead logging.
+///
+/// Before each write, `prepare` is called with the data about to be written
+/// and its future is driven to completion; if it fails, the copy fails
+/// without writing. After each successful write, `commit` is called with the
+/// number of bytes written; if its future fails, the copy fails with that
+/// error and those bytes are not counted as copied.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_two_phase<R, W, P, PFut, C, CFut>(reader: R, writer: W, buffer: Box<[u8]>,
+                                              prepare: P, commit: C)
+    -> CopyTwoPhase<R, W, P, PFut, C, CFut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          P: FnMut(&[u8]) -> PFut,
+          PFut: Future<Item = (), Error = io::Error>,
+          C: FnMut(usize) -> CFut,
+          CFut: Future<Item = (), Error = io::Error>,
+{
+    CopyTwoPhase {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        prepare,
+        prepare_fut: None,
+        prepared: false,
+        commit,
+        commit_fut: None,
+        committing: 0,
+    }
+}
+
+impl<R, W, P, PFut, C, CFut> fmt::Debug for CopyTwoPhase<R, W, P, PFut, C, CFut>
+    where R: fmt::Debug,
+          W: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyTwoPhase")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .field("prepared", &self.prepared)
+            .field("committing", &self.commit_fut.as_ref().map(|_| self.committing))
+            .finish()
+    }
+}
+
+impl<R, W, P, PFut, C, CFut> Future for CopyTwoPhase<R, W, P, PFut, C, CFut>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          P: FnMut(&[u8]) -> PFut,
+          PFut: Future<Item = (), Error = io::Error>,
+          C: FnMut(usize) -> CFut,
+          CFut: Future<Item = (), Error = io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, prepare, write and commit it!
+            while self.pos < self.cap {
+                if let Some(ref mut fut) = self.commit_fut {
+                    try_ready!(fut.poll());
+                    self.pos += self.committing;
+                    self.amt += self.committing as u64;
+                }
+                self.commit_fut = None;
+                if self.pos == self.cap {
+                    break;
+                }
+
+                let buf = self.buffer.as_ref().unwrap();
+                if !self.prepared {
+                    if self.prepare_fut.is_none() {
+                        self.prepare_fut = Some((self.prepare)(&buf[self.pos..self.cap]));
+                    }
+                    try_ready!(self.prepare_fut.as_mut().unwrap().poll());
+                    self.prepare_fut = None;
+                    self.prepared = true;
+                }
+
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                }
+                self.prepared = false;
+                self.committing = i;
+                self.commit_fut = Some((self.commit)(i));
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use std::io;
+use std::os::unix::io::AsRawFd;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::RawFd;
+#[cfg(target_os = "linux")]
+use std::{mem, ptr};
+
+#[cfg(target_os = "linux")]
+use libc;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which reads datagrams from a reader and sends them out in batches.
+///
+/// Created by the [`copy_udp_batch`] function.
+///
+/// [`copy_udp_batch`]: fn.copy_udp_batch.html
+#[derive(Debug)]
+pub struct CopyUdpBatch<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    slot_len: usize,
+    batch_size: usize,
+    lens: Vec<usize>,
+    sent: usize,
+    sending: bool,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for UDP proxies, where each read from
+/// `reader` is a complete datagram and `writer` is a connected datagram socket.
+///
+/// `buffer` is split into `batch_size` equal slots. Up to `batch_size` reads
+/// are gathered (fewer if the reader would block or ends), then sent with a
+/// single `sendmmsg` call on Linux. When the socket would block, or on other
+/// systems, datagrams are sent one at a time with `writer.write`.
+///
+/// On success the number of payload bytes sent is returned.
+///
+/// Available on Unix.
+///
+/// # Panics
+///
+/// Panics if `batch_size` is zero or larger than the buffer.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_udp_batch<R, W>(reader: R, socket: W, buffer: Box<[u8]>, batch_size: usize)
+    -> CopyUdpBatch<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite + AsRawFd,
+{
+    assert!(batch_size > 0, "batch size must not be zero");
+    assert!(batch_size <= buffer.len(), "batch size is larger than the buffer");
+    CopyUdpBatch {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(socket),
+        slot_len: buffer.len() / batch_size,
+        batch_size,
+        lens: Vec::with_capacity(batch_size),
+        sent: 0,
+        sending: false,
+        amt: 0,
+        buffer: Some(buffer),
+    }
+}
+
+/// Sends `datagrams` with one `sendmmsg` call, returning how many went out
+/// and their total length.
+#[cfg(target_os = "linux")]
+fn send_batch(fd: RawFd, datagrams: &[&[u8]]) -> io::Result<(usize, u64)> {
+    let mut iovecs: Vec<libc::iovec> = datagrams.iter().map(|d| libc::iovec {
+        iov_base: d.as_ptr() as *mut libc::c_void,
+        iov_len: d.len(),
+    }).collect();
+    let mut msgs: Vec<libc::mmsghdr> = iovecs.iter_mut().map(|iov| {
+        let mut msg: libc::mmsghdr = unsafe { mem::zeroed() };
+        msg.msg_hdr.msg_name = ptr::null_mut();
+        msg.msg_hdr.msg_iov = iov;
+        msg.msg_hdr.msg_iovlen = 1;
+        msg
+    }).collect();
+    let n = unsafe { libc::sendmmsg(fd, msgs.as_mut_ptr(), msgs.len() as libc::c_uint, 0) };
+    if n == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let n = n as usize;
+    let bytes = msgs[..n].iter().map(|m| m.msg_len as u64).sum();
+    Ok((n, bytes))
+}
+
+impl<R, W> CopyUdpBatch<R, W>
+    where W: AsyncWrite + AsRawFd,
+{
+    fn poll_send(&mut self) -> Poll<(), io::Error> {
+        let buf = self.buffer.as_ref().unwrap();
+        let writer = self.writer.as_mut().unwrap();
+        let (slot_len, lens) = (self.slot_len, &self.lens);
+        while self.sent < lens.len() {
+            #[cfg(target_os = "linux")]
+            {
+                let datagrams: Vec<&[u8]> = (self.sent..lens.len())
+                    .map(|k| &buf[k * slot_len..k * slot_len + lens[k]])
+                    .collect();
+                match send_batch(writer.as_raw_fd(), &datagrams) {
+                    Ok((n, bytes)) => {
+                        self.sent += n;
+                        self.amt += bytes;
+                        continue;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e),
+                }
+            }
+
+            // Sending one datagram through the writer also registers for
+            // readiness if the socket is still not ready.
+            let k = self.sent;
+            let datagram = &buf[k * slot_len..k * slot_len + lens[k]];
+            let i = try_nb!(writer.write(datagram));
+            if i == 0 && !datagram.is_empty() {
+                return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                          "write zero byte into writer"));
+            }
+            self.sent += 1;
+            self.amt += i as u64;
+        }
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<R, W> Future for CopyUdpBatch<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite + AsRawFd,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // Gather datagrams until the batch is full or the reader has
+            // nothing more for now.
+            while !self.sending && !self.read_done && self.lens.len() < self.batch_size {
+                let slot = self.lens.len() * self.slot_len;
+                let buf = &mut self.buffer.as_mut().unwrap()[slot..slot + self.slot_len];
+                match self.reader.as_mut().unwrap().read(buf) {
+                    Ok(0) => self.read_done = true,
+                    Ok(n) => self.lens.push(n),
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        if self.lens.is_empty() {
+                            return Ok(Async::NotReady);
+                        }
+                        break;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            if !self.lens.is_empty() {
+                self.sending = true;
+            }
+
+            // If we have a batch, let's send it out!
+            if self.sending {
+                try_ready!(self.poll_send());
+                self.lens.clear();
+                self.sent = 0;
+                self.sending = false;
+            }
+
+            // If we've sent all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.lens.is_empty() && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use {copy_with_buffer, Copy};
+
+/// A future which copies data from the reading side of a bidirectional
+/// stream into a writer.
+///
+/// Created by the [`copy_from_unified`] function.
+///
+/// [`copy_from_unified`]: fn.copy_from_unified.html
+#[derive(Debug)]
+pub struct CopyFromUnified<S, W> {
+    inner: Copy<S, W>,
+}
+
+/// Convenience variant of [`copy_with_buffer`] for a stream which is both
+/// `AsyncRead` and `AsyncWrite`, like a `TcpStream`, without splitting it first.
+///
+/// Only the reading side of `stream` is used. The whole `stream` is returned on
+/// success, so its writing side remains available afterwards.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_from_unified<S, W>(stream: S, writer: W, buffer: Box<[u8]>) -> CopyFromUnified<S, W>
+    where S: AsyncRead + AsyncWrite,
+          W: AsyncWrite,
+{
+    CopyFromUnified {
+        inner: copy_with_buffer(stream, writer, buffer),
+    }
+}
+
+impl<S, W> Future for CopyFromUnified<S, W>
+    where S: AsyncRead + AsyncWrite,
+          W: AsyncWrite,
+{
+    type Item = (u64, S, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, S, W, Box<[u8]>), io::Error> {
+        self.inner.poll()
+    }
+}
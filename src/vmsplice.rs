@@ -0,0 +1,317 @@
+use std::io::{self, PipeReader, PipeWriter, Read};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+
+use futures::{Async, Future, Poll};
+
+use libc;
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use fd_ready::FdReady;
+
+const CHUNK: usize = 65536;
+
+/// A future which copies data between two file descriptors through a pipe,
+/// without passing it through userspace.
+///
+/// Created by the [`copy_vmsplice`] function.
+///
+/// [`copy_vmsplice`]: fn.copy_vmsplice.html
+#[derive(Debug)]
+pub struct CopyVmsplice<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pipe: Option<(PipeReader, PipeWriter)>,
+    pending: usize,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    reader_ready: FdReady,
+    writer_ready: FdReady,
+    fallback: Option<Box<[u8]>>,
+}
+
+/// Creates a future which copies everything from `reader` into `writer` by
+/// moving up to 64 KiB at a time into an internal pipe with `splice(2)` and
+/// then from the pipe into `writer`, so the kernel never copies the data into
+/// a userspace buffer.
+///
+/// `vmsplice(2)` only moves userspace memory into a pipe, so both sides use
+/// `splice`, which accepts sockets, files and pipes. If either descriptor
+/// does not support splicing (`EINVAL` or `EBADF`), the copy continues like
+/// [`copy_with_buffer`] with an internal 64 KiB buffer, keeping anything
+/// already in the pipe.
+///
+/// While splicing, the future waits for the descriptors on the default
+/// reactor, registering duplicates of them next to the registrations of
+/// `reader` and `writer`. On success the number of bytes copied is returned.
+///
+/// Available on Linux with the `vmsplice` feature.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_vmsplice<R, W>(reader: R, writer: W) -> CopyVmsplice<R, W>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite + AsRawFd,
+{
+    CopyVmsplice {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        pipe: None,
+        pending: 0,
+        pos: 0,
+        cap: 0,
+        amt: 0,
+        reader_ready: FdReady::default(),
+        writer_ready: FdReady::default(),
+        fallback: None,
+    }
+}
+
+fn splice(fd_in: RawFd, fd_out: RawFd, len: usize) -> io::Result<usize> {
+    let ret = unsafe {
+        libc::splice(fd_in, ptr::null_mut(), fd_out, ptr::null_mut(), len,
+                     libc::SPLICE_F_MOVE | libc::SPLICE_F_NONBLOCK)
+    };
+    if ret == -1 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(ret as usize)
+    }
+}
+
+fn is_unsupported(e: &io::Error) -> bool {
+    matches!(e.raw_os_error(), Some(libc::EINVAL) | Some(libc::EBADF))
+}
+
+impl<R, W> CopyVmsplice<R, W>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite + AsRawFd,
+{
+    /// Moves data through the pipe. Returns `Ready(false)` if the descriptors
+    /// turn out not to support splicing.
+    fn poll_splice(&mut self) -> Poll<bool, io::Error> {
+        if self.pipe.is_none() {
+            self.pipe = Some(io::pipe()?);
+        }
+        let (pipe_in, pipe_out) = {
+            let pipe = self.pipe.as_ref().unwrap();
+            (pipe.0.as_raw_fd(), pipe.1.as_raw_fd())
+        };
+        loop {
+            // If the pipe is empty, fill it from the reader.
+            if self.pending == 0 && !self.read_done {
+                let fd = self.reader.as_ref().unwrap().as_raw_fd();
+                match splice(fd, pipe_out, CHUNK) {
+                    Ok(0) => self.read_done = true,
+                    Ok(n) => self.pending = n,
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        self.reader_ready.wait_readable(fd)?;
+                        return Ok(Async::NotReady);
+                    }
+                    Err(ref e) if is_unsupported(e) => return Ok(Async::Ready(false)),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            // If the pipe has some data, let's move it out!
+            if self.pending > 0 {
+                let fd = self.writer.as_ref().unwrap().as_raw_fd();
+                match splice(pipe_in, fd, self.pending) {
+                    Ok(0) => {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "splice moved zero bytes"));
+                    }
+                    Ok(n) => {
+                        self.pending -= n;
+                        self.amt += n as u64;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        self.writer_ready.wait_writable(fd)?;
+                        return Ok(Async::NotReady);
+                    }
+                    Err(ref e) if is_unsupported(e) => return Ok(Async::Ready(false)),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            if self.pending == 0 && self.read_done {
+                return Ok(Async::Ready(true));
+            }
+        }
+    }
+
+    /// Switches to copying through a buffer, taking over what is left in the
+    /// pipe.
+    fn start_fallback(&mut self) -> io::Result<()> {
+        let mut buffer = vec![0; CHUNK].into_boxed_slice();
+        if self.pending > 0 {
+            let pipe_reader = &mut self.pipe.as_mut().unwrap().0;
+            pipe_reader.read_exact(&mut buffer[..self.pending])?;
+            self.pos = 0;
+            self.cap = self.pending;
+            self.pending = 0;
+        }
+        self.pipe = None;
+        self.fallback = Some(buffer);
+        Ok(())
+    }
+}
+
+impl<R, W> Future for CopyVmsplice<R, W>
+    where R: AsyncRead + AsRawFd,
+          W: AsyncWrite + AsRawFd,
+{
+    type Item = (u64, R, W);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W), io::Error> {
+        if self.fallback.is_none() {
+            let spliced = try_ready!(self.poll_splice());
+            // Close the duplicates, the descriptors aren't waited on anymore.
+            self.reader_ready = FdReady::default();
+            self.writer_ready = FdReady::default();
+            if !spliced {
+                self.start_fallback()?;
+            }
+        }
+
+        while let Some(ref mut buf) = self.fallback {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            if self.pos == self.cap && self.read_done {
+                break;
+            }
+        }
+
+        // We've moved all the data and we've seen EOF, flush out the data and
+        // finish the transfer.
+        try_nb!(self.writer.as_mut().unwrap().flush());
+        let reader = self.reader.take().unwrap();
+        let writer = self.writer.take().unwrap();
+        Ok((self.amt, reader, writer).into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File, OpenOptions};
+    use std::io::{self, Read, Write};
+    use std::os::unix::io::{AsRawFd, RawFd};
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
+
+    use futures::{future, Async, Future, Poll};
+
+    use tokio_io::{AsyncRead, AsyncWrite};
+
+    use super::*;
+
+    /// Makes a plain descriptor usable with `copy_vmsplice`.
+    struct Fd<T>(T);
+
+    impl<T: Read> Read for Fd<T> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.0.read(buf)
+        }
+    }
+
+    impl<T: Read> AsyncRead for Fd<T> {}
+
+    impl<T: Write> Write for Fd<T> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()
+        }
+    }
+
+    impl<T: Write> AsyncWrite for Fd<T> {
+        fn shutdown(&mut self) -> Poll<(), io::Error> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    impl<T: AsRawFd> AsRawFd for Fd<T> {
+        fn as_raw_fd(&self) -> RawFd {
+            self.0.as_raw_fd()
+        }
+    }
+
+    #[test]
+    fn waits_for_a_stalled_reader() {
+        let (reader, mut feed) = io::pipe().unwrap();
+        let (mut out, writer) = io::pipe().unwrap();
+        let feeder = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            feed.write_all(b"late data").unwrap();
+        });
+
+        let mut copy = copy_vmsplice(Fd(reader), Fd(writer));
+        let mut polls = 0;
+        let (n, _, writer) = future::poll_fn(|| {
+            polls += 1;
+            copy.poll()
+        }).wait().unwrap();
+        feeder.join().unwrap();
+        drop(writer);
+
+        // A busy loop would poll thousands of times while the pipe is idle.
+        assert!(polls < 10, "polled {} times", polls);
+        assert_eq!(n, 9);
+        let mut data = Vec::new();
+        out.read_to_end(&mut data).unwrap();
+        assert_eq!(data, b"late data");
+    }
+
+    #[test]
+    fn falls_back_keeping_the_pipe_contents() {
+        let dir = env::temp_dir();
+        let src = dir.join(format!("vmsplice-src-{}", process::id()));
+        let dst = dir.join(format!("vmsplice-dst-{}", process::id()));
+        let data: Vec<u8> = (0..100_000).map(|i| (i % 251) as u8).collect();
+        fs::write(&src, &data).unwrap();
+        File::create(&dst).unwrap();
+
+        // Splicing into a file opened for appending fails with `EINVAL`, after
+        // the first chunk is already in the pipe.
+        let reader = Fd(File::open(&src).unwrap());
+        let writer = Fd(OpenOptions::new().append(true).open(&dst).unwrap());
+        let (n, _, _) = copy_vmsplice(reader, writer).wait().unwrap();
+        let copied = fs::read(&dst).unwrap();
+        fs::remove_file(&src).unwrap();
+        fs::remove_file(&dst).unwrap();
+        assert_eq!(n, data.len() as u64);
+        assert!(copied == data, "copied data differs");
+    }
+}
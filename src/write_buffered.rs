@@ -0,0 +1,136 @@
+use std::cmp;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], but gathers several reads before
+/// writing them out at once.
+///
+/// Created by the [`copy_write_buffered`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_write_buffered`]: fn.copy_write_buffered.html
+#[derive(Debug)]
+pub struct CopyWriteBuffered<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    acc_pos: usize,
+    acc_len: usize,
+    writing: bool,
+    write_threshold: usize,
+    amt: u64,
+    primary_buf: Option<Box<[u8]>>,
+    accumulate_buf: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for writers with high per-call overhead,
+/// like TLS with small records.
+///
+/// Data read into `primary_buf` is appended to `accumulate_buf` until it holds
+/// at least `write_threshold` bytes (or is full, or EOF is reached), and only
+/// then written out. Both buffers are returned on success.
+///
+/// # Panics
+///
+/// Panics if `write_threshold` is larger than `accumulate_buf`, or if
+/// `accumulate_buf` is empty.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_write_buffered<R, W>(reader: R, writer: W, primary_buf: Box<[u8]>,
+                                 accumulate_buf: Box<[u8]>, write_threshold: usize)
+    -> CopyWriteBuffered<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    assert!(!accumulate_buf.is_empty(), "accumulate buffer must not be empty");
+    assert!(write_threshold <= accumulate_buf.len(),
+            "write threshold is larger than the accumulate buffer");
+    CopyWriteBuffered {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        pos: 0,
+        cap: 0,
+        acc_pos: 0,
+        acc_len: 0,
+        writing: false,
+        write_threshold,
+        amt: 0,
+        primary_buf: Some(primary_buf),
+        accumulate_buf: Some(accumulate_buf),
+    }
+}
+
+impl<R, W> Future for CopyWriteBuffered<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = (u64, R, W, Box<[u8]>, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>, Box<[u8]>), io::Error> {
+        loop {
+            // If enough has been gathered, let's write it out!
+            if self.writing {
+                while self.acc_pos < self.acc_len {
+                    let acc = self.accumulate_buf.as_mut().unwrap();
+                    let writer = self.writer.as_mut().unwrap();
+                    let i = try_nb!(writer.write(&acc[self.acc_pos..self.acc_len]));
+                    if i == 0 {
+                        return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                                  "write zero byte into writer"));
+                    } else {
+                        self.acc_pos += i;
+                        self.amt += i as u64;
+                    }
+                }
+                self.acc_pos = 0;
+                self.acc_len = 0;
+                self.writing = false;
+            }
+
+            if self.pos < self.cap {
+                // Move what was read to the accumulate buffer, and start
+                // writing when it's time.
+                let buf = self.primary_buf.as_ref().unwrap();
+                let acc = self.accumulate_buf.as_mut().unwrap();
+                let len = cmp::min(acc.len() - self.acc_len, self.cap - self.pos);
+                acc[self.acc_len..self.acc_len + len].copy_from_slice(&buf[self.pos..self.pos + len]);
+                self.acc_len += len;
+                self.pos += len;
+                if self.acc_len == acc.len() || self.acc_len >= self.write_threshold {
+                    self.writing = true;
+                }
+            } else if !self.read_done {
+                // If our buffer is empty, then we need to read some data to
+                // continue.
+                let buf = self.primary_buf.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            } else if self.acc_len > 0 {
+                // Write out the rest after EOF.
+                self.writing = true;
+            } else {
+                // If we've written all the data and we've seen EOF, flush out
+                // the data and finish the transfer.
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let primary_buf = self.primary_buf.take().unwrap();
+                let accumulate_buf = self.accumulate_buf.take().unwrap();
+                return Ok((self.amt, reader, writer, primary_buf, accumulate_buf).into())
+            }
+        }
+    }
+}
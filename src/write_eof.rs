@@ -0,0 +1,177 @@
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Outcome of [`copy_with_write_eof_ok`].
+///
+/// [`copy_with_write_eof_ok`]: fn.copy_with_write_eof_ok.html
+#[derive(Debug)]
+pub enum CopyResult<R, W> {
+    /// Everything was copied and the writer was flushed.
+    Completed {
+        /// Number of bytes written.
+        bytes: u64,
+        /// The reader.
+        reader: R,
+        /// The writer.
+        writer: W,
+        /// The copy buffer.
+        buffer: Box<[u8]>,
+    },
+    /// The writer stopped accepting data before the reader hit EOF.
+    WriterClosed {
+        /// Number of bytes written before the writer closed.
+        bytes: u64,
+        /// The reader.
+        reader: R,
+        /// The writer, not flushed.
+        writer: W,
+        /// The copy buffer.
+        buffer: Box<[u8]>,
+    },
+}
+
+/// A future which copies data like [`Copy`], but treats a writer accepting
+/// zero bytes as closed rather than as an error.
+///
+/// Created by the [`copy_with_write_eof_ok`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_write_eof_ok`]: fn.copy_with_write_eof_ok.html
+#[derive(Debug)]
+pub struct CopyWriteEofOk<R, W> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    write_eof: bool,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] for writers which signal that they are
+/// done by returning `Ok(0)`, like a closed channel.
+///
+/// Instead of failing with `WriteZero`, the copy then stops and resolves to
+/// [`CopyResult::WriterClosed`]; the bytes not taken by the writer are lost.
+/// A complete transfer resolves to [`CopyResult::Completed`].
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+/// [`CopyResult::WriterClosed`]: enum.CopyResult.html#variant.WriterClosed
+/// [`CopyResult::Completed`]: enum.CopyResult.html#variant.Completed
+pub fn copy_with_write_eof_ok<R, W>(reader: R, writer: W, buffer: Box<[u8]>)
+    -> CopyWriteEofOk<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    CopyWriteEofOk {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        write_eof: false,
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R, W> Future for CopyWriteEofOk<R, W>
+    where R: AsyncRead,
+          W: AsyncWrite,
+{
+    type Item = CopyResult<R, W>;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<CopyResult<R, W>, io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    self.write_eof = true;
+                    break;
+                } else {
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If the writer is closed, stop right away.
+            if self.write_eof {
+                return Ok(CopyResult::WriterClosed {
+                    bytes: self.amt,
+                    reader: self.reader.take().unwrap(),
+                    writer: self.writer.take().unwrap(),
+                    buffer: self.buffer.take().unwrap(),
+                }.into())
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                return Ok(CopyResult::Completed {
+                    bytes: self.amt,
+                    reader: self.reader.take().unwrap(),
+                    writer: self.writer.take().unwrap(),
+                    buffer: self.buffer.take().unwrap(),
+                }.into())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn writer_closed_mid_transfer() {
+        let data: Vec<u8> = (0..25).collect();
+        let writer = Cursor::new(vec![0; 10].into_boxed_slice());
+        match copy_with_write_eof_ok(Cursor::new(data.clone()), writer,
+                                     vec![0; 4].into_boxed_slice()).wait().unwrap() {
+            CopyResult::WriterClosed { bytes, reader, writer, .. } => {
+                assert_eq!(bytes, 10);
+                assert_eq!(reader.position(), 12);
+                assert_eq!(&writer.get_ref()[..], &data[..10]);
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+
+    #[test]
+    fn completed() {
+        let data: Vec<u8> = (0..25).collect();
+        match copy_with_write_eof_ok(Cursor::new(data.clone()), Cursor::new(Vec::new()),
+                                     vec![0; 4].into_boxed_slice()).wait().unwrap() {
+            CopyResult::Completed { bytes, writer, .. } => {
+                assert_eq!(bytes, 25);
+                assert_eq!(writer.into_inner(), data);
+            }
+            r => panic!("{:?}", r),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::fmt;
+use std::io;
+
+use futures::{Async, Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], but can switch to another writer
+/// when a write fails.
+///
+/// Created by the [`copy_with_write_failover`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_write_failover`]: fn.copy_with_write_failover.html
+pub struct CopyWriteFailover<R, W, F> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    on_write_error: F,
+}
+
+/// Variant of [`copy_with_buffer`] which hands a failed writer and its error
+/// to `on_write_error` instead of failing right away.
+///
+/// The callback either returns a replacement writer, and the rest of the
+/// current chunk is retried on it, or returns an error to fail the copy with.
+/// A write of zero bytes counts as a failure with `WriteZero`. Flush errors
+/// are not recovered.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_write_failover<R, W, F>(reader: R, writer: W, buffer: Box<[u8]>,
+                                         on_write_error: F)
+    -> CopyWriteFailover<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(io::Error, W) -> Result<W, io::Error>,
+{
+    CopyWriteFailover {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        buffer: Some(buffer),
+        on_write_error,
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F> fmt::Debug for CopyWriteFailover<R, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyWriteFailover")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W, F> Future for CopyWriteFailover<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(io::Error, W) -> Result<W, io::Error>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out, switching
+            // writers if needed!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let res = self.writer.as_mut().unwrap().write(&buf[self.pos..self.cap]);
+                let err = match res {
+                    Ok(0) => io::Error::new(io::ErrorKind::WriteZero,
+                                            "write zero byte into writer"),
+                    Ok(i) => {
+                        self.pos += i;
+                        self.amt += i as u64;
+                        continue;
+                    }
+                    Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => {
+                        return Ok(Async::NotReady);
+                    }
+                    Err(e) => e,
+                };
+                let failed = self.writer.take().unwrap();
+                self.writer = Some((self.on_write_error)(err, failed)?);
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,132 @@
+use std::cmp;
+use std::fmt;
+use std::io::{self, IoSlice};
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`], writing a generated header
+/// together with each chunk in one vectored write.
+///
+/// Created by the [`copy_write_gather`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_write_gather`]: fn.copy_write_gather.html
+pub struct CopyWriteGather<R, W, F> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    header: Vec<u8>,
+    header_pos: usize,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    buffer: Option<Box<[u8]>>,
+    header_fn: F,
+}
+
+/// Variant of [`copy_with_buffer`] for framing protocols: after each read,
+/// `header_fn` makes a header for the chunk, and both go out with
+/// `write_vectored`, saving a syscall per chunk on writers which support it.
+///
+/// `header_fn` is called synchronously and should be cheap. The number of
+/// payload bytes, headers excluded, is returned on success.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_write_gather<R, W, F>(reader: R, writer: W, buffer: Box<[u8]>, header_fn: F)
+    -> CopyWriteGather<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(&[u8]) -> Vec<u8>,
+{
+    CopyWriteGather {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        header: Vec::new(),
+        header_pos: 0,
+        pos: 0,
+        cap: 0,
+        amt: 0,
+        buffer: Some(buffer),
+        header_fn,
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F> fmt::Debug for CopyWriteGather<R, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyWriteGather")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("header", &self.header)
+            .field("header_pos", &self.header_pos)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W, F> Future for CopyWriteGather<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(&[u8]) -> Vec<u8>,
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue, and make a header for it.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.header = (self.header_fn)(&buf[..n]);
+                    self.header_pos = 0;
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out, along with
+            // whatever is left of the header.
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let header = &self.header[self.header_pos..];
+                let i = if header.is_empty() {
+                    try_nb!(writer.write(&buf[self.pos..self.cap]))
+                } else {
+                    try_nb!(writer.write_vectored(&[IoSlice::new(header),
+                                                    IoSlice::new(&buf[self.pos..self.cap])]))
+                };
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                }
+                let h = cmp::min(i, header.len());
+                self.header_pos += h;
+                self.pos += i - h;
+                self.amt += (i - h) as u64;
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::fmt;
+use std::io;
+
+use futures::{Future, Poll};
+
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// A future which copies data like [`Copy`] and shows every written slice to
+/// a closure.
+///
+/// Created by the [`copy_with_write_observer`] function.
+///
+/// [`Copy`]: struct.Copy.html
+/// [`copy_with_write_observer`]: fn.copy_with_write_observer.html
+pub struct CopyWriteObserver<R, W, F> {
+    reader: Option<R>,
+    read_done: bool,
+    writer: Option<W>,
+    pos: usize,
+    cap: usize,
+    amt: u64,
+    on_write: F,
+    buffer: Option<Box<[u8]>>,
+}
+
+/// Variant of [`copy_with_buffer`] which calls `on_write` with exactly the bytes
+/// accepted by each successful `write` call.
+///
+/// Because of partial writes, a slice given to `on_write` may be shorter than
+/// the chunk which was read. Only bytes committed to the writer are observed.
+///
+/// [`copy_with_buffer`]: fn.copy_with_buffer.html
+pub fn copy_with_write_observer<R, W, F>(reader: R, writer: W, buffer: Box<[u8]>, on_write: F)
+    -> CopyWriteObserver<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(&[u8]),
+{
+    CopyWriteObserver {
+        reader: Some(reader),
+        read_done: false,
+        writer: Some(writer),
+        amt: 0,
+        pos: 0,
+        cap: 0,
+        on_write,
+        buffer: Some(buffer),
+    }
+}
+
+impl<R: fmt::Debug, W: fmt::Debug, F> fmt::Debug for CopyWriteObserver<R, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CopyWriteObserver")
+            .field("reader", &self.reader)
+            .field("read_done", &self.read_done)
+            .field("writer", &self.writer)
+            .field("pos", &self.pos)
+            .field("cap", &self.cap)
+            .field("amt", &self.amt)
+            .field("buffer", &self.buffer)
+            .finish()
+    }
+}
+
+impl<R, W, F> Future for CopyWriteObserver<R, W, F>
+    where R: AsyncRead,
+          W: AsyncWrite,
+          F: FnMut(&[u8]),
+{
+    type Item = (u64, R, W, Box<[u8]>);
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<(u64, R, W, Box<[u8]>), io::Error> {
+        loop {
+            // If our buffer is empty, then we need to read some data to
+            // continue.
+            if self.pos == self.cap && !self.read_done {
+                let buf = self.buffer.as_mut().unwrap();
+                let reader = self.reader.as_mut().unwrap();
+                let n = try_nb!(reader.read(buf));
+                if n == 0 {
+                    self.read_done = true;
+                } else {
+                    self.pos = 0;
+                    self.cap = n;
+                }
+            }
+
+            // If our buffer has some data, let's write it out!
+            while self.pos < self.cap {
+                let buf = self.buffer.as_mut().unwrap();
+                let writer = self.writer.as_mut().unwrap();
+                let i = try_nb!(writer.write(&buf[self.pos..self.cap]));
+                if i == 0 {
+                    return Err(io::Error::new(io::ErrorKind::WriteZero,
+                                              "write zero byte into writer"));
+                } else {
+                    (self.on_write)(&buf[self.pos..self.pos + i]);
+                    self.pos += i;
+                    self.amt += i as u64;
+                }
+            }
+
+            // If we've written all the data and we've seen EOF, flush out the
+            // data and finish the transfer.
+            if self.pos == self.cap && self.read_done {
+                try_nb!(self.writer.as_mut().unwrap().flush());
+                let reader = self.reader.take().unwrap();
+                let writer = self.writer.take().unwrap();
+                let buffer = self.buffer.take().unwrap();
+                return Ok((self.amt, reader, writer, buffer).into())
+            }
+        }
+    }
+}